@@ -0,0 +1,667 @@
+use crate::files;
+use chip_assembler::assembler::Assembler;
+use chip_assembler::source_map::SourceMap;
+use chip_interpreter::debugger::{Debugger, Register, StopReason};
+use chip_interpreter::interpreter::Interpreter;
+use serde_json::{json, Value};
+use std::fs::{read, read_to_string};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREAD_ID: i64 = 1;
+const REGISTERS_REFERENCE: i64 = 1;
+const STACK_REFERENCE: i64 = 2;
+
+// Debug Adapter Protocol server, editors launch it with `chip debug-adapter` and talk to it
+// over stdin and stdout.
+pub struct DebugAdapter<W> {
+    output: W,
+    seq: i64,
+    session: Option<Session>,
+    running: bool,
+}
+
+struct Session {
+    debugger: Debugger,
+    source: Option<PathBuf>,
+    source_map: SourceMap,
+    stop_on_entry: bool,
+}
+
+impl<W: Write> DebugAdapter<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            output,
+            seq: 1,
+            session: None,
+            running: false,
+        }
+    }
+
+    // Serves requests read from `input` until the client disconnects.
+    pub fn run(mut self, input: impl Read + Send + 'static) -> io::Result<()> {
+        let requests = spawn_reader(input);
+        let frame_duration = Duration::from_nanos(1_000_000_000 / 60);
+        let mut next_frame = Instant::now();
+
+        loop {
+            let request = if self.running {
+                match requests.try_recv() {
+                    Ok(request) => Some(request),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            } else {
+                match requests.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return Ok(()),
+                }
+            };
+
+            if let Some(request) = request {
+                if !self.handle(&request?)? {
+                    return Ok(());
+                }
+
+                continue;
+            }
+
+            // The program runs at its normal speed while the editor isn't looking.
+            self.run_frame()?;
+
+            next_frame += frame_duration;
+            thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    // Handles one request, returns false once the client disconnected.
+    pub fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+
+        let body = match command {
+            "initialize" => {
+                self.respond(
+                    request,
+                    Ok(json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsReadMemoryRequest": true,
+                    })),
+                )?;
+
+                return self.event("initialized", json!({})).map(|_| true);
+            }
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "configurationDone" => {
+                self.respond(request, Ok(json!({})))?;
+
+                match &self.session {
+                    Some(session) if session.stop_on_entry => self.stopped("entry", None)?,
+                    Some(_) => self.running = true,
+                    None => (),
+                }
+
+                return Ok(true);
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                    { "name": "Stack", "variablesReference": STACK_REFERENCE, "expensive": false },
+                ]
+            })),
+            "variables" => self.variables(arguments),
+            "readMemory" => self.read_memory(arguments),
+            "continue" => {
+                self.respond(request, Ok(json!({ "allThreadsContinued": true })))?;
+                self.running = self.session.is_some();
+
+                return Ok(true);
+            }
+            "pause" => {
+                self.respond(request, Ok(json!({})))?;
+
+                if self.running {
+                    self.running = false;
+                    self.stopped("pause", None)?;
+                }
+
+                return Ok(true);
+            }
+            "next" | "stepIn" | "stepOut" => {
+                self.respond(request, Ok(json!({})))?;
+                self.step(command)?;
+
+                return Ok(true);
+            }
+            "disconnect" | "terminate" => {
+                self.respond(request, Ok(json!({})))?;
+
+                return Ok(false);
+            }
+            _ => Err(format!("Unsupported request {}", command)),
+        };
+
+        self.respond(request, body)?;
+
+        Ok(true)
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["program"]
+            .as_str()
+            .ok_or("Missing program to launch")?;
+        let path = Path::new(path);
+
+        // ROMs are loaded as is, everything else is assembled first.
+        let (program, source, source_map) = if path.extension().map_or(false, |e| e == "ch8") {
+            let program = read(path).map_err(|err| err.to_string())?;
+
+            (program, None, SourceMap::default())
+        } else {
+            let source = read_to_string(path).map_err(|err| err.to_string())?;
+            let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let program = Assembler::from(source.as_str())
+                .with_files(files::loader(dir))
+                .assemble()
+                .map_err(|diagnostics| {
+                    diagnostics
+                        .iter()
+                        .map(|diagnostic| diagnostic.render(&source))
+                        .collect::<Vec<_>>()
+                        .join("\n\n")
+                })?;
+
+            (
+                program.bytes().to_vec(),
+                Some(path.to_path_buf()),
+                program.source_map().clone(),
+            )
+        };
+
+        let mut interpreter = Interpreter::default();
+        interpreter.load(&program).map_err(|err| err.to_string())?;
+
+        self.session = Some(Session {
+            debugger: Debugger::new(interpreter),
+            source,
+            source_map,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+        });
+
+        Ok(json!({}))
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session.as_mut().ok_or("No program launched")?;
+        let lines = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        for &(address, _) in session.source_map.entries() {
+            session.debugger.remove_breakpoint(address);
+        }
+
+        let breakpoints: Vec<Value> = lines
+            .iter()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+
+                match session.source_map.address(line) {
+                    Some((address, line)) => {
+                        session.debugger.set_breakpoint(address, None);
+                        json!({ "verified": true, "line": line })
+                    }
+                    None => json!({ "verified": false, "message": "No code on this line" }),
+                }
+            })
+            .collect();
+
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let session = self.session.as_ref().ok_or("No program launched")?;
+        let debugger = &session.debugger;
+
+        // The current instruction, then the calls leading to it, innermost first. The stack
+        // holds the address of each call instruction.
+        let addresses = Some(debugger.register(Register::Pc))
+            .into_iter()
+            .chain(debugger.stack().iter().rev().copied());
+
+        let frames: Vec<Value> = addresses
+            .enumerate()
+            .map(|(id, address)| {
+                let mut frame = json!({
+                    "id": id,
+                    "name": format!("{:#05X}", address),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("{:#X}", address),
+                });
+
+                if let (Some(source), Some(line)) =
+                    (&session.source, session.source_map.line(address))
+                {
+                    frame["source"] = json!({ "path": source });
+                    frame["line"] = json!(line);
+                    frame["column"] = json!(1);
+                }
+
+                frame
+            })
+            .collect();
+
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session.as_ref().ok_or("No program launched")?;
+        let debugger = &session.debugger;
+
+        let variable = |name: String, value: u16, digits: usize| {
+            json!({
+                "name": name,
+                "value": format!("{:#0width$X}", value, width = digits + 2),
+                "variablesReference": 0,
+            })
+        };
+
+        let variables: Vec<Value> = match arguments["variablesReference"].as_i64() {
+            Some(REGISTERS_REFERENCE) => (0..16)
+                .map(|x| (format!("V{:X}", x), Register::V(x), 2))
+                .chain([
+                    ("I".to_string(), Register::I, 3),
+                    ("PC".to_string(), Register::Pc, 3),
+                    ("SP".to_string(), Register::Sp, 2),
+                    ("DT".to_string(), Register::Dt, 2),
+                    ("ST".to_string(), Register::St, 2),
+                ])
+                .map(|(name, register, digits)| variable(name, debugger.register(register), digits))
+                .collect(),
+            Some(STACK_REFERENCE) => debugger
+                .stack()
+                .iter()
+                .enumerate()
+                .map(|(index, &address)| variable(format!("[{}]", index), address, 3))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value, String> {
+        let session = self.session.as_ref().ok_or("No program launched")?;
+        let memory = session.debugger.memory();
+
+        let reference = arguments["memoryReference"].as_str().unwrap_or("0");
+        let base = parse_address(reference).ok_or("Invalid memory reference")?;
+        let offset = arguments["offset"].as_i64().unwrap_or(0);
+        let start = (base as i64).saturating_add(offset).max(0) as usize;
+        let count = arguments["count"].as_u64().unwrap_or(0) as usize;
+
+        let start = start.min(memory.len());
+        let end = start.saturating_add(count).min(memory.len());
+
+        Ok(json!({
+            "address": format!("{:#X}", start),
+            "data": base64(&memory[start..end]),
+            "unreadableBytes": count - (end - start),
+        }))
+    }
+
+    fn step(&mut self, command: &str) -> io::Result<()> {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+
+        let result = match command {
+            "next" => session.debugger.step_over(),
+            "stepOut" => session.debugger.step_out(),
+            _ => session.debugger.step(),
+        };
+
+        match result {
+            Ok(StopReason::Breakpoint(_)) => self.stopped("breakpoint", None),
+            Ok(StopReason::Halted) => self.terminated(),
+            Ok(_) => self.stopped("step", None),
+            Err(err) => self.stopped("exception", Some(err.to_string())),
+        }
+    }
+
+    fn run_frame(&mut self) -> io::Result<()> {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => {
+                self.running = false;
+                return Ok(());
+            }
+        };
+
+        let result = session.debugger.run_frame();
+
+        match result {
+            Ok(StopReason::FrameEnd) => Ok(()),
+            Ok(StopReason::Halted) => {
+                self.running = false;
+                self.terminated()
+            }
+            Ok(StopReason::Breakpoint(_)) => {
+                self.running = false;
+                self.stopped("breakpoint", None)
+            }
+            Ok(_) => {
+                self.running = false;
+                self.stopped("step", None)
+            }
+            Err(err) => {
+                self.running = false;
+                self.stopped("exception", Some(err.to_string()))
+            }
+        }
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<String>) -> io::Result<()> {
+        self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "description": description,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        )
+    }
+
+    fn terminated(&mut self) -> io::Result<()> {
+        self.event("exited", json!({ "exitCode": 0 }))?;
+        self.event("terminated", json!({}))
+    }
+
+    fn respond(&mut self, request: &Value, body: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": body.is_ok(),
+        });
+
+        match body {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+
+        let message = message.to_string();
+
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )?;
+
+        self.output.flush()
+    }
+}
+
+// Reads `Content-Length` framed messages on a separate thread, so a running program can be
+// paused.
+fn spawn_reader(input: impl Read + Send + 'static) -> Receiver<io::Result<Value>> {
+    let (sender, receiver) = channel();
+
+    thread::spawn(move || {
+        let mut input = BufReader::new(input);
+
+        loop {
+            let message = read_message(&mut input);
+            let done = !matches!(message, Ok(Some(_)));
+
+            if let Some(message) = message.transpose() {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+
+            if done {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut len = None;
+
+    loop {
+        let mut header = String::new();
+
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            len = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let len = len.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No Content-Length"))?;
+    let mut body = vec![0; len];
+    input.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn parse_address(reference: &str) -> Option<u16> {
+    match reference
+        .strip_prefix("0x")
+        .or(reference.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => reference.parse().ok(),
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+            bits | (byte as u32) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, read_message, DebugAdapter};
+    use serde_json::{json, Value};
+    use std::env::temp_dir;
+    use std::fs::write;
+    use std::io::Cursor;
+
+    fn messages(output: &[u8]) -> Vec<Value> {
+        let mut input = Cursor::new(output);
+        let mut messages = Vec::new();
+
+        while let Some(message) = read_message(&mut input).unwrap() {
+            messages.push(message);
+        }
+
+        messages
+    }
+
+    fn request(seq: i64, command: &str, arguments: Value) -> Value {
+        json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&[0x00, 0xE0, 0xFF]), "AOD/");
+    }
+
+    #[test]
+    fn test_session() {
+        let path = temp_dir().join("chip_debug_adapter_test.asm");
+        write(&path, "cls\ncall 0x206\njmp 0x202\n\nret\n").unwrap();
+
+        let mut adapter = DebugAdapter::new(Vec::new());
+
+        adapter
+            .handle(&request(1, "initialize", json!({})))
+            .unwrap();
+        adapter
+            .handle(&request(
+                2,
+                "launch",
+                json!({ "program": path, "stopOnEntry": true }),
+            ))
+            .unwrap();
+        adapter
+            .handle(&request(
+                3,
+                "setBreakpoints",
+                json!({ "source": { "path": path }, "breakpoints": [{ "line": 4 }] }),
+            ))
+            .unwrap();
+        adapter
+            .handle(&request(4, "configurationDone", json!({})))
+            .unwrap();
+        adapter.handle(&request(5, "next", json!({}))).unwrap();
+        adapter.handle(&request(6, "stepIn", json!({}))).unwrap();
+        adapter
+            .handle(&request(7, "stackTrace", json!({})))
+            .unwrap();
+        adapter
+            .handle(&request(8, "variables", json!({ "variablesReference": 1 })))
+            .unwrap();
+        adapter
+            .handle(&request(
+                9,
+                "readMemory",
+                json!({ "memoryReference": "0x200", "offset": 1, "count": 2 }),
+            ))
+            .unwrap();
+        adapter
+            .handle(&request(
+                10,
+                "readMemory",
+                json!({ "memoryReference": "0xFFF", "offset": i64::MAX, "count": u64::MAX }),
+            ))
+            .unwrap();
+        assert!(!adapter
+            .handle(&request(11, "disconnect", json!({})))
+            .unwrap());
+
+        let messages = messages(&adapter.output);
+        let response = |seq: i64| {
+            messages
+                .iter()
+                .find(|message| message["request_seq"] == seq)
+                .unwrap()
+        };
+
+        assert!(messages
+            .iter()
+            .any(|message| message["event"] == "initialized"));
+
+        // The breakpoint moves to the next line with code.
+        assert_eq!(
+            response(3)["body"]["breakpoints"][0],
+            json!({ "verified": true, "line": 5 })
+        );
+
+        let stopped: Vec<&Value> = messages
+            .iter()
+            .filter(|message| message["event"] == "stopped")
+            .map(|message| &message["body"]["reason"])
+            .collect();
+        assert_eq!(stopped, ["entry", "step", "step"]);
+
+        // Inside the subroutine, called from line 2.
+        let frames = &response(7)["body"]["stackFrames"];
+        assert_eq!(frames[0]["line"], 5);
+        assert_eq!(frames[0]["instructionPointerReference"], "0x206");
+        assert_eq!(frames[1]["line"], 2);
+
+        let variables = &response(8)["body"]["variables"];
+        assert_eq!(variables[17]["name"], "PC");
+        assert_eq!(variables[17]["value"], "0x206");
+
+        // CLS is 0x00E0, CALL 0x206 is 0x2206.
+        assert_eq!(
+            response(9)["body"],
+            json!({ "address": "0x201", "data": base64(&[0xE0, 0x22]), "unreadableBytes": 0 })
+        );
+
+        // Nothing past the end of memory can be read.
+        assert_eq!(
+            response(10)["body"],
+            json!({ "address": "0x1000", "data": "", "unreadableBytes": u64::MAX })
+        );
+    }
+
+    #[test]
+    fn test_launch_errors() {
+        let path = temp_dir().join("chip_debug_adapter_errors.asm");
+        write(&path, "jmp\ncls\nld v0, nowhere\n").unwrap();
+
+        let mut adapter = DebugAdapter::new(Vec::new());
+
+        adapter
+            .handle(&request(1, "launch", json!({ "program": path })))
+            .unwrap();
+
+        // Every diagnostic is reported, not just the first one.
+        let response = &messages(&adapter.output)[0];
+        let message = response["message"].as_str().unwrap();
+        assert_eq!(response["success"], false);
+        assert!(message.contains("The line ended in the middle of a statement"));
+        assert!(message.contains("The symbol nowhere isn't defined"));
+    }
+}
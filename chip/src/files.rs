@@ -0,0 +1,12 @@
+use std::fs::read;
+use std::path::PathBuf;
+
+// Loads the files `incbin` and `include` name, relative to `dir`. The contents are leaked as the
+// assembler borrows them for as long as it lives, which is most of the run of the CLI.
+pub fn loader<'a>(dir: PathBuf) -> impl FnMut(&str) -> Option<&'a [u8]> {
+    move |path| {
+        let bytes = read(dir.join(path)).ok()?;
+
+        Some(Box::leak(bytes.into_boxed_slice()))
+    }
+}
@@ -0,0 +1,186 @@
+use chip_format::config::{Case, Config, Immediates};
+use clap::{Args, ValueEnum};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+const CONFIG_FILE: &str = "chipfmt.toml";
+
+// Settings given on the command line, these win over the ones in `chipfmt.toml`.
+#[derive(Debug, Args)]
+pub struct Overrides {
+    #[arg(long)]
+    indent: Option<usize>,
+    #[arg(long)]
+    operand_column: Option<usize>,
+    #[arg(long)]
+    comment_column: Option<usize>,
+    #[arg(long)]
+    case: Option<CaseArg>,
+    #[arg(long)]
+    immediates: Option<ImmediatesArg>,
+    #[arg(long)]
+    max_blank_lines: Option<usize>,
+}
+
+impl Overrides {
+    fn apply(&self, config: &mut Config) {
+        if let Some(indent) = self.indent {
+            config.indent = indent;
+        }
+
+        if let Some(operand_column) = self.operand_column {
+            config.operand_column = operand_column;
+        }
+
+        if let Some(comment_column) = self.comment_column {
+            config.comment_column = comment_column;
+        }
+
+        if let Some(case) = self.case {
+            config.case = case.into();
+        }
+
+        if let Some(immediates) = self.immediates {
+            config.immediates = immediates.into();
+        }
+
+        if let Some(max_blank_lines) = self.max_blank_lines {
+            config.max_blank_lines = max_blank_lines;
+        }
+    }
+}
+
+// The choices of `Case` and `Immediates` for clap, which `chip_format` doesn't depend on.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CaseArg {
+    Lower,
+    Upper,
+}
+
+impl From<CaseArg> for Case {
+    fn from(case: CaseArg) -> Self {
+        match case {
+            CaseArg::Lower => Case::Lower,
+            CaseArg::Upper => Case::Upper,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ImmediatesArg {
+    Keep,
+    Hex,
+    Decimal,
+}
+
+impl From<ImmediatesArg> for Immediates {
+    fn from(immediates: ImmediatesArg) -> Self {
+        match immediates {
+            ImmediatesArg::Keep => Immediates::Keep,
+            ImmediatesArg::Hex => Immediates::Hex,
+            ImmediatesArg::Decimal => Immediates::Decimal,
+        }
+    }
+}
+
+// The settings for formatting `path` and the `chipfmt.toml` they were read from.
+pub fn config(path: &Path, overrides: &Overrides) -> Result<(Config, Option<PathBuf>), String> {
+    let mut config = Config::default();
+    let file = find(path);
+
+    if let Some(file) = &file {
+        load(file, &mut config).map_err(|err| format!("{}: {}", file.display(), err))?;
+    }
+
+    overrides.apply(&mut config);
+
+    Ok((config, file))
+}
+
+// The closest `chipfmt.toml`, in the directory of `path` or any above it.
+fn find(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|file| file.is_file())
+}
+
+fn load(file: &Path, config: &mut Config) -> Result<(), String> {
+    let text = read_to_string(file).map_err(|err| err.to_string())?;
+    let table = text.parse::<Table>().map_err(|err| err.to_string())?;
+
+    for (key, value) in table {
+        let value = match value {
+            Value::String(value) => value,
+            Value::Integer(value) => value.to_string(),
+            value => return Err(format!("{} isn't a valid {}", value, key)),
+        };
+
+        config.set(&key, &value).map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{config, CaseArg, Overrides};
+    use chip_format::config::Case;
+    use clap::Parser;
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn test_config() {
+        let root = temp_dir().join(format!("chipfmt-{}", std::process::id()));
+        let dir = root.join("src/sprites");
+        create_dir_all(&dir).unwrap();
+
+        write(root.join("chipfmt.toml"), "indent = 2\ncase = \"upper\"\n").unwrap();
+
+        let mut overrides = Overrides {
+            indent: None,
+            operand_column: None,
+            comment_column: None,
+            case: None,
+            immediates: None,
+            max_blank_lines: Some(0),
+        };
+
+        let (found, file) = config(&dir.join("draw.asm"), &overrides).unwrap();
+        assert_eq!(file, Some(root.join("chipfmt.toml")));
+        assert_eq!(
+            (found.indent, found.case, found.max_blank_lines),
+            (2, Case::Upper, 0)
+        );
+
+        overrides.case = Some(CaseArg::Lower);
+        let (found, _) = config(&dir.join("draw.asm"), &overrides).unwrap();
+        assert_eq!(found.case, Case::Lower);
+
+        write(root.join("chipfmt.toml"), "tabs = true\n").unwrap();
+        assert!(config(&dir.join("draw.asm"), &overrides).is_err());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[derive(Debug, Parser)]
+    struct Command {
+        #[command(flatten)]
+        overrides: Overrides,
+    }
+
+    #[test]
+    fn test_overrides() {
+        let command = Command::try_parse_from(["chip", "--case", "upper", "--immediates", "hex"]);
+        let overrides = command.unwrap().overrides;
+
+        assert!(matches!(overrides.case, Some(CaseArg::Upper)));
+
+        let error = Command::try_parse_from(["chip", "--case", "title"]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("[possible values: lower, upper]"));
+    }
+}
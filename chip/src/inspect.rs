@@ -0,0 +1,335 @@
+use chip_assembler::parser::Instruction;
+use chip_disassembler::decoder::size;
+use chip_disassembler::disassembler::{Disassembler, Line, Listing};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+
+// Most rows drawn for a single sprite, the size of a SUPER-CHIP 16x16 sprite in bytes.
+const MAX_SPRITE_ROWS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chip8 => write!(f, "CHIP-8"),
+            Self::SuperChip => write!(f, "SUPER-CHIP"),
+            Self::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+// Static analysis of a ROM, for looking at it without running it.
+pub struct Inspection {
+    size: usize,
+    sha1: String,
+    crc32: u32,
+    families: BTreeMap<&'static str, (Platform, usize)>,
+    // Addresses of a full 64 KiB ROM end past `u16::MAX`.
+    code: Vec<Range<u32>>,
+    data: Vec<Range<u32>>,
+    sprites: Vec<(u16, Vec<u8>)>,
+    warnings: Vec<String>,
+}
+
+impl Inspection {
+    pub fn new(rom: &[u8]) -> Self {
+        let listing = Disassembler::from(rom).disassemble();
+
+        let mut families = BTreeMap::new();
+
+        for (_, instruction) in listing.instructions() {
+            let (family, platform) = family(&instruction);
+            families.entry(family).or_insert((platform, 0)).1 += 1;
+        }
+
+        let (code, data) = regions(&listing);
+
+        Self {
+            size: rom.len(),
+            sha1: sha1_smol::Sha1::from(rom).digest().to_string(),
+            crc32: crc32fast::hash(rom),
+            families,
+            sprites: sprites(&listing, &data),
+            warnings: self_modifying(&listing, &code),
+            code,
+            data,
+        }
+    }
+
+    // Oldest platform that runs every instruction found.
+    pub fn platform(&self) -> Platform {
+        self.families
+            .values()
+            .map(|&(platform, _)| platform)
+            .max()
+            .unwrap_or(Platform::Chip8)
+    }
+}
+
+impl Display for Inspection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let len = |ranges: &[Range<u32>]| ranges.iter().map(|r| r.len()).sum::<usize>();
+
+        writeln!(f, "Size      {} bytes", self.size)?;
+        writeln!(f, "SHA-1     {}", self.sha1)?;
+        writeln!(f, "CRC32     {:08x}", self.crc32)?;
+        writeln!(f, "Platform  {}", self.platform())?;
+        writeln!(f, "Reachable {} bytes", len(&self.code))?;
+        writeln!(f, "Other     {} bytes", len(&self.data))?;
+
+        writeln!(f, "\nInstructions")?;
+
+        for (family, (platform, count)) in &self.families {
+            writeln!(f, "  {:<20} {:>5}  {}", family, count, platform)?;
+        }
+
+        if !self.data.is_empty() {
+            writeln!(f, "\nUnreachable")?;
+
+            for range in &self.data {
+                writeln!(f, "  0x{:03X}..0x{:03X}", range.start, range.end)?;
+            }
+        }
+
+        for (address, rows) in &self.sprites {
+            writeln!(f, "\nSprite at 0x{:03X}", address)?;
+
+            for row in rows {
+                let pixels: String = (0..8)
+                    .map(|bit| if row << bit & 0x80 != 0 { '#' } else { '.' })
+                    .collect();
+
+                writeln!(f, "  {}  0x{:02X}", pixels, row)?;
+            }
+        }
+
+        if !self.warnings.is_empty() {
+            writeln!(f, "\nWarnings")?;
+
+            for warning in &self.warnings {
+                writeln!(f, "  {}", warning)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn family(instruction: &Instruction) -> (&'static str, Platform) {
+    use Platform::*;
+
+    match instruction {
+        Instruction::Cls => ("00E0 cls", Chip8),
+        Instruction::Ret => ("00EE ret", Chip8),
+        Instruction::JmpAddress(_) => ("1NNN jmp", Chip8),
+        Instruction::Call(_) => ("2NNN call", Chip8),
+        Instruction::SeRegVal(..) => ("3XNN se", Chip8),
+        Instruction::SneRegVal(..) => ("4XNN sne", Chip8),
+        Instruction::SeRegReg(..) => ("5XY0 se", Chip8),
+        Instruction::LdRegVal(..) => ("6XNN ld", Chip8),
+        Instruction::AddRegVal(..) => ("7XNN add", Chip8),
+        Instruction::LdRegReg(..) => ("8XY0 ld", Chip8),
+        Instruction::Or(..) => ("8XY1 or", Chip8),
+        Instruction::And(..) => ("8XY2 and", Chip8),
+        Instruction::Xor(..) => ("8XY3 xor", Chip8),
+        Instruction::AddRegReg(..) => ("8XY4 add", Chip8),
+        Instruction::Sub(..) => ("8XY5 sub", Chip8),
+        Instruction::Shr(..) => ("8XY6 shr", Chip8),
+        Instruction::Subn(..) => ("8XY7 subn", Chip8),
+        Instruction::Shl(..) => ("8XYE shl", Chip8),
+        Instruction::SneRegReg(..) => ("9XY0 sne", Chip8),
+        Instruction::LdIndex(..) => ("ANNN ld i", Chip8),
+        Instruction::JmpRegAddress(..) => ("BNNN jmp v0", Chip8),
+        Instruction::Rnd(..) => ("CXNN rnd", Chip8),
+        Instruction::Drw(_, _, 0) => ("DXY0 drw 16x16", SuperChip),
+        Instruction::Drw(..) => ("DXYN drw", Chip8),
+        Instruction::Skp(_) => ("EX9E skp", Chip8),
+        Instruction::Skpn(_) => ("EXA1 sknp", Chip8),
+        Instruction::LdRegDelay(..) => ("FX07 ld vx, dt", Chip8),
+        Instruction::LdRegKey(..) => ("FX0A ld vx, k", Chip8),
+        Instruction::LdDelayReg(..) => ("FX15 ld dt", Chip8),
+        Instruction::LdSoundReg(..) => ("FX18 ld st", Chip8),
+        Instruction::AddIndexReg(..) => ("FX1E add i", Chip8),
+        Instruction::LdFReg(_) => ("FX29 ld f", Chip8),
+        Instruction::LdBReg(_) => ("FX33 ld b", Chip8),
+        Instruction::LdMemIndexReg(_) => ("FX55 ld [i]", Chip8),
+        Instruction::LdRegMemIndex(_) => ("FX65 ld vx, [i]", Chip8),
+        Instruction::Scd(_) => ("00CN scd", SuperChip),
+        Instruction::Scr => ("00FB scr", SuperChip),
+        Instruction::Scl => ("00FC scl", SuperChip),
+        Instruction::Exit => ("00FD exit", SuperChip),
+        Instruction::Low => ("00FE low", SuperChip),
+        Instruction::High => ("00FF high", SuperChip),
+        Instruction::LdHfReg(_) => ("FX30 ld hf", SuperChip),
+        Instruction::LdRReg(_) => ("FX75 ld r", SuperChip),
+        Instruction::LdRegR(_) => ("FX85 ld vx, r", SuperChip),
+        Instruction::LdIndexLong(_) => ("F000 ld i, long", XoChip),
+        Instruction::Save(..) => ("5XY2 save", XoChip),
+        Instruction::Load(..) => ("5XY3 load", XoChip),
+        Instruction::Plane(_) => ("FN01 plane", XoChip),
+        Instruction::Audio => ("F002 audio", XoChip),
+        Instruction::Pitch(_) => ("FX3A pitch", XoChip),
+    }
+}
+
+// Splits the ROM into the runs of reachable instructions and everything else.
+fn regions(listing: &Listing) -> (Vec<Range<u32>>, Vec<Range<u32>>) {
+    let mut code: Vec<Range<u32>> = Vec::new();
+    let mut data: Vec<Range<u32>> = Vec::new();
+
+    for line in listing.lines() {
+        let (regions, len) = match line {
+            Line::Instruction { instruction, .. } => (&mut code, size(instruction) as u32),
+            Line::Data { bytes, .. } => (&mut data, bytes.len() as u32),
+        };
+
+        let start = line.address() as u32;
+
+        match regions.last_mut() {
+            Some(last) if last.end == start => last.end = start + len,
+            _ => regions.push(start..start + len),
+        }
+    }
+
+    (code, data)
+}
+
+// Data that `I` is pointed at, which is most likely sprites.
+fn sprites(listing: &Listing, data: &[Range<u32>]) -> Vec<(u16, Vec<u8>)> {
+    let mut targets: Vec<u16> = listing
+        .instructions()
+        .filter_map(|(_, instruction)| match instruction {
+            Instruction::LdIndex(_, address) => Some(address),
+            _ => None,
+        })
+        .collect();
+
+    targets.sort_unstable();
+    targets.dedup();
+
+    targets
+        .into_iter()
+        .filter_map(|address| {
+            let region = data
+                .iter()
+                .find(|region| region.contains(&(address as u32)))?;
+            let rows = (region.end - address as u32).min(MAX_SPRITE_ROWS as u32);
+
+            let bytes = listing
+                .lines()
+                .iter()
+                .filter_map(|line| match line {
+                    Line::Data { address, bytes } => Some((*address, bytes)),
+                    Line::Instruction { .. } => None,
+                })
+                .flat_map(|(start, bytes)| (start as u32..).zip(bytes.iter().copied()))
+                .skip_while(|&(other, _)| other < address as u32)
+                .take(rows as usize)
+                .map(|(_, byte)| byte)
+                .collect();
+
+            Some((address, bytes))
+        })
+        .collect()
+}
+
+// Points `I` lands on reachable code while the ROM also writes through `I`.
+fn self_modifying(listing: &Listing, code: &[Range<u32>]) -> Vec<String> {
+    let writes = listing.instructions().any(|(_, instruction)| {
+        matches!(
+            instruction,
+            Instruction::LdMemIndexReg(_) | Instruction::LdBReg(_) | Instruction::Save(..)
+        )
+    });
+
+    if !writes {
+        return Vec::new();
+    }
+
+    listing
+        .instructions()
+        .filter_map(|(address, instruction)| match instruction {
+            Instruction::LdIndex(_, target) | Instruction::LdIndexLong(target) => {
+                Some((address, target))
+            }
+            _ => None,
+        })
+        .filter(|(_, target)| code.iter().any(|range| range.contains(&(*target as u32))))
+        .map(|(address, target)| {
+            format!(
+                "0x{:03X} points I at code at 0x{:03X}, which may be modified",
+                address, target
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Inspection, Platform};
+
+    #[test]
+    fn test_inspect() {
+        let rom = [
+            0xA2, 0x08, // ld i, 0x208
+            0xD0, 0x13, // drw v0, v1, 3
+            0x12, 0x04, // jmp 0x204
+            0x00, 0xFF, // unreachable
+            0x18, 0x3C, 0x7E, // sprite
+        ];
+
+        let inspection = Inspection::new(&rom);
+        let report = inspection.to_string();
+
+        assert_eq!(inspection.platform(), Platform::Chip8);
+        assert!(inspection.warnings.is_empty());
+        assert!(report.contains("Size      11 bytes"));
+        assert!(report.contains("Reachable 6 bytes"));
+        assert!(report.contains("0x206..0x20B"));
+        assert!(report.contains("Sprite at 0x208\n  ...##...  0x18\n  ..####..  0x3C\n"));
+    }
+
+    #[test]
+    fn test_full_size_rom() {
+        // Fills all of memory after 0x200, the data runs up to the end of it.
+        let mut rom = vec![0; 0x10000 - 0x200];
+
+        rom[..4].copy_from_slice(&[0xA3, 0x00, 0x12, 0x02]);
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0x3C]);
+
+        let inspection = Inspection::new(&rom);
+        let report = inspection.to_string();
+
+        assert!(report.contains("Size      65024 bytes"));
+        assert!(report.contains("Reachable 4 bytes"));
+        assert!(report.contains("Other     65020 bytes"));
+        assert!(report.contains("0x204..0x10000"));
+        assert!(report.contains("Sprite at 0x300\n  ...##...  0x18\n  ..####..  0x3C\n"));
+        assert_eq!(inspection.sprites[0].1.len(), 32);
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        let rom = [
+            0x00, 0xFF, // high
+            0xA2, 0x00, // ld i, 0x200
+            0xF0, 0x55, // ld [i], v0
+            0x12, 0x00, // jmp 0x200
+        ];
+
+        let inspection = Inspection::new(&rom);
+
+        assert_eq!(inspection.platform(), Platform::SuperChip);
+        assert_eq!(
+            inspection.warnings,
+            ["0x202 points I at code at 0x200, which may be modified"]
+        );
+    }
+}
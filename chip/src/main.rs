@@ -1,8 +1,18 @@
 use chip_assembler::assembler::Assembler;
+use chip_format::formatter::Formatter;
 use clap::{Parser, Subcommand};
+use debug_adapter::DebugAdapter;
+use inspect::Inspection;
 use std::env::current_dir;
-use std::fs::{read_to_string, write};
+use std::fs::{read, read_to_string, write};
+use std::io::{stdin, stdout};
 use std::path::PathBuf;
+use std::process::exit;
+
+mod debug_adapter;
+mod files;
+mod format;
+mod inspect;
 
 #[derive(Debug, Parser)]
 #[command(version)]
@@ -13,9 +23,27 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    Run { path: PathBuf },
-    Build { path: PathBuf },
-    Format { path: PathBuf },
+    Run {
+        path: PathBuf,
+    },
+    Build {
+        path: PathBuf,
+    },
+    Format {
+        path: PathBuf,
+        // Fails instead of writing when the file isn't formatted.
+        #[arg(long)]
+        check: bool,
+        // Prints the settings from `chipfmt.toml` and the overrides instead of formatting.
+        #[arg(long)]
+        print_config: bool,
+        #[command(flatten)]
+        overrides: format::Overrides,
+    },
+    Inspect {
+        path: PathBuf,
+    },
+    DebugAdapter,
 }
 
 fn main() {
@@ -30,25 +58,102 @@ fn main() {
             print_blue_bar("COMP");
             println!("{}", path.as_os_str().to_str().unwrap());
 
-            let bytes = Assembler::from(file.as_str())
-                .flat_map(|opcode| opcode.to_le_bytes())
-                .collect::<Vec<u8>>();
+            let dir = path.parent().unwrap().to_path_buf();
+
+            let program = Assembler::from(file.as_str())
+                .with_files(files::loader(dir))
+                .assemble();
+
+            let program = match program {
+                Ok(program) => program,
+                Err(diagnostics) => {
+                    println!();
+
+                    for diagnostic in &diagnostics {
+                        eprintln!("{}\n", diagnostic.render(&file));
+                    }
+
+                    print_red_bar("FAIL");
+                    match diagnostics.len() {
+                        1 => println!("1 error"),
+                        count => println!("{} errors", count),
+                    }
+                    exit(1);
+                }
+            };
 
             let out = current_dir().unwrap().join("out.ch8");
 
-            write(&out, bytes).unwrap();
+            write(&out, program.bytes()).unwrap();
 
             print_green_bar("DONE");
             print!("File saved at ({})", out.as_os_str().to_str().unwrap());
         }
-        Commands::Format { path: _ } => todo!(),
+        Commands::Format {
+            path,
+            check,
+            print_config,
+            overrides,
+        } => {
+            let path = current_dir().unwrap().join(path);
+
+            let (config, config_file) = match format::config(&path, &overrides) {
+                Ok(config) => config,
+                Err(err) => {
+                    print_red_bar("FAIL");
+                    println!("{}", err);
+                    exit(1);
+                }
+            };
+
+            if print_config {
+                if let Some(config_file) = config_file {
+                    println!("# {}", config_file.display());
+                }
+
+                print!("{}", config);
+                return;
+            }
+
+            let file = read_to_string(&path).unwrap();
+
+            let formatted = Formatter::from(file.as_str()).with_config(config).format();
+
+            if formatted == file {
+                return;
+            }
+
+            if check {
+                // The first line that changes, or the first missing one.
+                let line = file
+                    .lines()
+                    .zip(formatted.lines())
+                    .position(|(line, formatted)| line != formatted)
+                    .unwrap_or_else(|| file.lines().count().min(formatted.lines().count()));
+
+                print_red_bar("DIFF");
+                println!("{}:{}", path.as_os_str().to_str().unwrap(), line + 1);
+                exit(1);
+            }
+
+            write(&path, formatted).unwrap();
+
+            print_green_bar("DONE");
+            println!("{}", path.as_os_str().to_str().unwrap());
+        }
+        Commands::Inspect { path } => {
+            let rom = read(current_dir().unwrap().join(path)).unwrap();
+
+            print!("{}", Inspection::new(&rom));
+        }
+        Commands::DebugAdapter => DebugAdapter::new(stdout()).run(stdin()).unwrap(),
     }
 }
 
 fn print_green_bar(text: &str) {
     // ANSI escape codes for green background and text
     let green_bg = "\x1b[48;5;40m"; // ANSI escape code for green background
-    let black_text = "\x1b[30m"; 
+    let black_text = "\x1b[30m";
     let reset = "\x1b[0m"; // ANSI escape code to reset colors
 
     // Print the text within the green bar
@@ -58,11 +163,24 @@ fn print_green_bar(text: &str) {
     print!("{}", reset);
 }
 
+fn print_red_bar(text: &str) {
+    // ANSI escape codes for red background and text
+    let red_bg = "\x1b[48;5;196m"; // ANSI escape code for red background
+    let black_text = "\x1b[30m";
+    let reset = "\x1b[0m"; // ANSI escape code to reset colors
+
+    // Print the text within the red bar
+    print!("{}  {}{}  {} ", red_bg, black_text, text, reset);
+
+    // Reset colors
+    print!("{}", reset);
+}
+
 fn print_blue_bar(text: &str) {
     // ANSI escape codes for blue background and text
     let blue_bg = "\x1b[48;5;33m"; // ANSI escape code for blue background
-    let black_text = "\x1b[30m"; 
-    let reset = "\x1b[0m";         // ANSI escape code to reset colors
+    let black_text = "\x1b[30m";
+    let reset = "\x1b[0m"; // ANSI escape code to reset colors
 
     // Print the text within the blue bar
     print!("{}  {}{}  {} ", blue_bg, black_text, text, reset);
@@ -17,20 +17,18 @@ fn main() {
 
     let parser = Parser::from(lexer);
 
-    let assembler = Assembler::from(parser).collect::<Vec<u16>>();
+    let assembler = Assembler::from(parser).collect::<Vec<u8>>();
 
     print_vec_as_hex(&assembler);
-
-    loop {}
 }
 
-fn print_vec_as_hex(data: &Vec<u16>) {
+fn print_vec_as_hex(data: &[u8]) {
     print!("[");
     for (index, value) in data.iter().enumerate() {
         if index > 0 {
             print!(", ");
         }
-        print!("0x{:04X}", value);
+        print!("0x{:02X}", value);
     }
     println!("]");
 }
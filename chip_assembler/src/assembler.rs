@@ -1,14 +1,48 @@
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
 use chip_lexer::lexer::Lexer;
 
-use crate::parser::{Instruction, Parser};
+use crate::diagnostic::Diagnostic;
+use crate::parser::{Instruction, Parser, ParserError, Statement};
+use crate::source_map::SourceMap;
+
+// Most passes laying out the program before giving up on the symbols settling.
+const MAX_LAYOUT_PASSES: usize = 16;
+
+// Reads the files named by `incbin` and `include`, the contents have to outlive the assembler.
+pub trait FileLoader<'a> {
+    fn load(&mut self, path: &str) -> Option<&'a [u8]>;
+}
+
+impl<'a, F: FnMut(&str) -> Option<&'a [u8]>> FileLoader<'a> for F {
+    fn load(&mut self, path: &str) -> Option<&'a [u8]> {
+        self(path)
+    }
+}
 
 pub struct Assembler<'a> {
     parser: Parser<'a>,
+    files: Option<Box<dyn FileLoader<'a> + 'a>>,
+    // Bytes of the last statement, not yet returned.
+    output: VecDeque<u8>,
+    layout: Layout,
+    source_map: SourceMap,
+    // Whether the first pass over the program collected the symbols yet.
+    laid_out: bool,
 }
 
 impl<'a> From<Parser<'a>> for Assembler<'a> {
     fn from(parser: Parser<'a>) -> Self {
-        Self { parser }
+        Self {
+            parser,
+            files: None,
+            output: VecDeque::new(),
+            layout: Layout::default(),
+            source_map: SourceMap::default(),
+            laid_out: false,
+        }
     }
 }
 
@@ -17,49 +51,610 @@ impl<'a> From<&'a str> for Assembler<'a> {
         let lexer = Lexer::from(value);
         let parser = Parser::from(lexer);
 
-        Self { parser }
+        Self::from(parser)
+    }
+}
+
+impl<'a> Assembler<'a> {
+    // Without files every `incbin` and `include` fails.
+    pub fn with_files(mut self, files: impl FileLoader<'a> + 'a) -> Self {
+        self.files = Some(Box::new(files));
+        self
+    }
+
+    // Lines of the instructions assembled so far.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    // Values of the labels and constants, empty until assembling started.
+    pub fn symbols(&self) -> &BTreeMap<&'a str, u16> {
+        self.parser.symbols()
+    }
+
+    // Errors found so far in the order of the source, complete once the program is assembled.
+    pub fn errors(&self) -> &[Diagnostic<'a>] {
+        self.parser.errors()
+    }
+
+    // Assembles the whole program, which only succeeds without a single error.
+    pub fn assemble(mut self) -> Result<Program<'a>, Vec<Diagnostic<'a>>> {
+        let bytes = self.by_ref().collect();
+
+        if !self.errors().is_empty() {
+            return Err(self.errors().to_vec());
+        }
+
+        Ok(Program {
+            bytes,
+            symbols: self.symbols().clone(),
+            source_map: self.source_map,
+        })
+    }
+
+    // The first passes only lay out the program to find the value of every symbol, its errors
+    // are reported by the last one. Symbols used before they are defined change the size of
+    // e.g. `fill`, so the layout is repeated with the values of the previous pass until they
+    // stop changing.
+    fn collect_symbols(&mut self) -> BTreeMap<&'a str, u16> {
+        let mut symbols = BTreeMap::new();
+
+        for _ in 0..MAX_LAYOUT_PASSES {
+            let mut parser = self.parser.clone().with_symbols(symbols.clone());
+            let mut layout = Layout::default();
+
+            while let Some(statement) = parser.next() {
+                match statement {
+                    Statement::Label(label) => {
+                        let _ = parser.define(label, layout.address);
+                    }
+                    Statement::Include(path) => {
+                        if let Ok(source) = load_source(&mut self.files, path) {
+                            let _ = parser.include(path, source);
+                        }
+                    }
+                    statement => {
+                        let _ = layout.place(&statement, &mut self.files);
+                    }
+                }
+            }
+
+            if parser.symbols() == &symbols {
+                break;
+            }
+
+            symbols = parser.symbols().clone();
+        }
+
+        symbols
     }
 }
 
 impl<'a> Iterator for Assembler<'a> {
-    type Item = u16;
+    type Item = u8;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.parser.next().map(|instruction| match instruction {
-            Instruction::Cls => 0x00E0,
-            Instruction::Ret => 0x00EE,
-            Instruction::JmpAddress(address) => 0x1000 | (address & 0x0FFF),
-            Instruction::Call(address) => 0x2000 | (address & 0x0FFF),
-            Instruction::SeRegVal(vx, nn) => 0x3000 | vx << 8 | nn,
-            Instruction::SneRegVal(vx, nn) => 0x4000 | vx << 8 | nn,
-            Instruction::SeRegReg(vx, vy) => 0x5000 | vx << 8 | vy << 4,
-            Instruction::LdRegVal(vx, nn) => 0x6000 | vx << 8 | nn,
-            Instruction::AddRegVal(vx, nn) => 0x7000 | vx << 8 | nn,
-            Instruction::LdRegReg(vx, vy) => 0x8000 | vx << 8 | vy << 4,
-            Instruction::Or(vx, vy) => 0x8001 | vx << 8 | vy << 4,
-            Instruction::And(vx, vy) => 0x8002 | vx << 8 | vy << 4,
-            Instruction::Xor(vx, vy) => 0x8003 | vx << 8 | vy << 4,
-            Instruction::AddRegReg(vx, vy) => 0x8004 | vx << 8 | vy << 4,
-            Instruction::Sub(vx, vy) => 0x8005 | vx << 8 | vy << 4,
-            Instruction::Shr(vx, vy) => 0x8006 | vx << 8 | vy << 4, 
-            Instruction::Subn(vx, vy) => 0x8007 | vx << 8 | vy << 4,
-            Instruction::Shl(vx, vy) => 0x800E | vx << 8 | vy << 4,
-            Instruction::SneRegReg(vx, vy) => 0x9000 | vx << 8 | vy << 4,
-            Instruction::LdIndex(i, address) => 0xA000 | i << 8 | (address & 0x0FFF),
-            Instruction::JmpRegAddress(vx, address) => 0xB000 | vx << 8 | (address & 0x0FFF),
-            Instruction::Rnd(vx, nn) => 0xC000 | vx << 8 | nn,
-            Instruction::Drw(vx, vy, nn) => 0xD000 | vx << 8 | vy << 4 | nn,
-            Instruction::Skp(vx) => 0xE09E | vx << 8,
-            Instruction::Skpn(vx) => 0xE0A1 | vx << 8,
-            Instruction::LdRegDelay(vx, dt) => 0xF007 | vx << 8 | dt << 4,
-            Instruction::LdRegKey(vx, key) => 0xF00A | vx << 8 | key << 4,
-            Instruction::LdDelayReg(dt, vx) => 0xF015 | vx << 8 | dt << 4,
-            Instruction::LdSoundReg(st, vx) => 0xF018 | vx << 8 | st << 4,
-            Instruction::AddIndexReg(i, vx) => 0xF01E | vx << 8 | i << 4,
-            Instruction::LdFReg(vx) => 0xF029 | vx << 8,
-            Instruction::LdBReg(vx) => 0xF033 | vx << 8,
-            Instruction::LdMemIndexReg(vx) => 0xF055 | vx << 8,
-            Instruction::LdRegMemIndex(vx) => 0xF065 | vx << 8,
-        })
+        if !self.laid_out {
+            let symbols = self.collect_symbols();
+
+            self.parser = self.parser.clone().with_symbols(symbols);
+            self.laid_out = true;
+        }
+
+        while self.output.is_empty() {
+            let statement = self.parser.next()?;
+
+            let result = match statement {
+                Statement::Label(label) => {
+                    let address = self.layout.address;
+                    let laid_out = self.parser.symbols().get(label).copied();
+
+                    // Code using the label was assembled with the address of the layout.
+                    self.parser.define(label, address).and_then(|_| {
+                        match laid_out == Some(address) {
+                            true => Ok(Vec::new()),
+                            false => Err(ParserError::UnstableSymbol(label)),
+                        }
+                    })
+                }
+                Statement::Include(path) => load_source(&mut self.files, path)
+                    .and_then(|source| self.parser.include(path, source))
+                    .map(|_| Vec::new()),
+                Statement::Instruction(_) => {
+                    self.source_map
+                        .push(self.layout.address, self.parser.line());
+                    self.layout.place(&statement, &mut self.files)
+                }
+                statement => self.layout.place(&statement, &mut self.files),
+            };
+
+            match result {
+                Ok(bytes) => self.output.extend(bytes),
+                Err(err) => self.parser.report(err),
+            }
+        }
+
+        self.output.pop_front()
+    }
+}
+
+// An assembled program without errors.
+#[derive(Debug, Clone)]
+pub struct Program<'a> {
+    bytes: Vec<u8>,
+    source_map: SourceMap,
+    symbols: BTreeMap<&'a str, u16>,
+}
+
+impl<'a> Program<'a> {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    pub fn symbols(&self) -> &BTreeMap<&'a str, u16> {
+        &self.symbols
+    }
+}
+
+// Where the next statement goes, programs are loaded at 0x200.
+struct Layout {
+    address: u16,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self { address: 0x200 }
+    }
+}
+
+impl Layout {
+    // Bytes of `statement`, labels, constants and includes have none.
+    fn place<'a>(
+        &mut self,
+        statement: &Statement<'a>,
+        files: &mut Option<Box<dyn FileLoader<'a> + 'a>>,
+    ) -> Result<Vec<u8>, ParserError<'a>> {
+        let bytes = match *statement {
+            Statement::Label(_) | Statement::Constant(..) | Statement::Include(_) => Vec::new(),
+            Statement::Instruction(instruction) => match encode(instruction) {
+                (word, None) => word.to_be_bytes().to_vec(),
+                (word, Some(second)) => [word.to_be_bytes(), second.to_be_bytes()].concat(),
+            },
+            Statement::Org(address) => {
+                let padding = address
+                    .checked_sub(self.address)
+                    .ok_or(ParserError::OrgBackwards(address))?;
+
+                vec![0; padding as usize]
+            }
+            Statement::Byte(ref values) => values.iter().map(|&value| value as u8).collect(),
+            Statement::Word(ref values) => values
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect(),
+            Statement::Align(0) => Vec::new(),
+            Statement::Align(alignment) => {
+                let padding = (alignment - self.address % alignment) % alignment;
+
+                vec![0; padding as usize]
+            }
+            Statement::Fill(count, value) => vec![value as u8; count as usize],
+            Statement::Incbin(path) => load(files, path)?.to_vec(),
+        };
+
+        self.address = self.address.wrapping_add(bytes.len() as u16);
+
+        Ok(bytes)
+    }
+}
+
+fn load<'a>(
+    files: &mut Option<Box<dyn FileLoader<'a> + 'a>>,
+    path: &'a str,
+) -> Result<&'a [u8], ParserError<'a>> {
+    files
+        .as_mut()
+        .and_then(|files| files.load(path))
+        .ok_or(ParserError::UnreadableFile(path))
+}
+
+fn load_source<'a>(
+    files: &mut Option<Box<dyn FileLoader<'a> + 'a>>,
+    path: &'a str,
+) -> Result<&'a str, ParserError<'a>> {
+    let bytes = load(files, path)?;
+
+    core::str::from_utf8(bytes).map_err(|_| ParserError::UnreadableFile(path))
+}
+
+// Opcode of `instruction`, and the second word for the four byte ones.
+pub fn encode(instruction: Instruction) -> (u16, Option<u16>) {
+    let word = match instruction {
+        Instruction::Cls => 0x00E0,
+        Instruction::Ret => 0x00EE,
+        Instruction::JmpAddress(address) => 0x1000 | (address & 0x0FFF),
+        Instruction::Call(address) => 0x2000 | (address & 0x0FFF),
+        Instruction::SeRegVal(vx, nn) => 0x3000 | vx << 8 | nn,
+        Instruction::SneRegVal(vx, nn) => 0x4000 | vx << 8 | nn,
+        Instruction::SeRegReg(vx, vy) => 0x5000 | vx << 8 | vy << 4,
+        Instruction::LdRegVal(vx, nn) => 0x6000 | vx << 8 | nn,
+        Instruction::AddRegVal(vx, nn) => 0x7000 | vx << 8 | nn,
+        Instruction::LdRegReg(vx, vy) => 0x8000 | vx << 8 | vy << 4,
+        Instruction::Or(vx, vy) => 0x8001 | vx << 8 | vy << 4,
+        Instruction::And(vx, vy) => 0x8002 | vx << 8 | vy << 4,
+        Instruction::Xor(vx, vy) => 0x8003 | vx << 8 | vy << 4,
+        Instruction::AddRegReg(vx, vy) => 0x8004 | vx << 8 | vy << 4,
+        Instruction::Sub(vx, vy) => 0x8005 | vx << 8 | vy << 4,
+        Instruction::Shr(vx, vy) => 0x8006 | vx << 8 | vy << 4,
+        Instruction::Subn(vx, vy) => 0x8007 | vx << 8 | vy << 4,
+        Instruction::Shl(vx, vy) => 0x800E | vx << 8 | vy << 4,
+        Instruction::SneRegReg(vx, vy) => 0x9000 | vx << 8 | vy << 4,
+        Instruction::LdIndex(i, address) => 0xA000 | i << 8 | (address & 0x0FFF),
+        Instruction::JmpRegAddress(vx, address) => 0xB000 | vx << 8 | (address & 0x0FFF),
+        Instruction::Rnd(vx, nn) => 0xC000 | vx << 8 | nn,
+        Instruction::Drw(vx, vy, nn) => 0xD000 | vx << 8 | vy << 4 | nn,
+        Instruction::Skp(vx) => 0xE09E | vx << 8,
+        Instruction::Skpn(vx) => 0xE0A1 | vx << 8,
+        Instruction::LdRegDelay(vx, dt) => 0xF007 | vx << 8 | dt << 4,
+        Instruction::LdRegKey(vx, key) => 0xF00A | vx << 8 | key << 4,
+        Instruction::LdDelayReg(dt, vx) => 0xF015 | vx << 8 | dt << 4,
+        Instruction::LdSoundReg(st, vx) => 0xF018 | vx << 8 | st << 4,
+        Instruction::AddIndexReg(i, vx) => 0xF01E | vx << 8 | i << 4,
+        Instruction::LdFReg(vx) => 0xF029 | vx << 8,
+        Instruction::LdBReg(vx) => 0xF033 | vx << 8,
+        Instruction::LdMemIndexReg(vx) => 0xF055 | vx << 8,
+        Instruction::LdRegMemIndex(vx) => 0xF065 | vx << 8,
+        Instruction::Scd(n) => 0x00C0 | (n & 0x000F),
+        Instruction::Scr => 0x00FB,
+        Instruction::Scl => 0x00FC,
+        Instruction::Exit => 0x00FD,
+        Instruction::Low => 0x00FE,
+        Instruction::High => 0x00FF,
+        Instruction::LdHfReg(vx) => 0xF030 | vx << 8,
+        Instruction::LdRReg(vx) => 0xF075 | vx << 8,
+        Instruction::LdRegR(vx) => 0xF085 | vx << 8,
+        Instruction::LdIndexLong(address) => return (0xF000, Some(address)),
+        Instruction::Save(vx, vy) => 0x5002 | vx << 8 | vy << 4,
+        Instruction::Load(vx, vy) => 0x5003 | vx << 8 | vy << 4,
+        Instruction::Plane(n) => 0xF001 | (n & 0x000F) << 8,
+        Instruction::Audio => 0xF002,
+        Instruction::Pitch(vx) => 0xF03A | vx << 8,
+    };
+
+    (word, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Assembler;
+    use crate::diagnostic::Diagnostic;
+    use crate::parser::ParserError;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use chip_interpreter::interpreter::Interpreter;
+    use chip_lexer::token::{Directive, Keyword, Mnemonic, Register, Token};
+
+    #[test]
+    fn test_source_map() {
+        let source = "cls\n\n; comment\ncall 0x208\njmp 0x202 ; loop\n\nret\n";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+        assert_eq!(bytes, [0x00, 0xE0, 0x22, 0x08, 0x12, 0x02, 0x00, 0xEE]);
+
+        let source_map = assembler.source_map();
+        assert_eq!(
+            source_map.entries(),
+            &[(0x200, 1), (0x202, 4), (0x204, 5), (0x206, 7)]
+        );
+        assert_eq!(source_map.line(0x204), Some(5));
+        assert_eq!(source_map.line(0x205), None);
+        assert_eq!(source_map.address(2), Some((0x202, 4)));
+        assert_eq!(source_map.address(8), None);
+    }
+
+    #[test]
+    fn test_labels() {
+        let source =
+            "start:\n    call draw\nloop: jmp loop\ndraw:\n    jmp end\n    jmp start\nend:\n";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        assert_eq!(bytes, [0x22, 0x04, 0x12, 0x02, 0x12, 0x08, 0x12, 0x00]);
+        assert_eq!(assembler.symbols().get("draw"), Some(&0x204));
+        assert!(assembler.errors().is_empty());
+    }
+
+    #[test]
+    fn test_label_errors() {
+        let source = "twice:\ntwice: jmp nowhere\ncls";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        // The undefined label still takes up its instruction, keeping the layout intact.
+        assert_eq!(bytes, [0x10, 0x00, 0x00, 0xE0]);
+
+        let errors: Vec<&ParserError> = assembler.errors().iter().map(Diagnostic::error).collect();
+        assert!(matches!(
+            errors[..],
+            [
+                ParserError::DuplicateSymbol("twice"),
+                ParserError::UndefinedSymbol("nowhere")
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_directives() {
+        let source = "org 0x300\nstart: db 1, 2, 3\nalign 2\ndw start, 0x1234\nfill 3, 0xFF\n\
+                      org 0x30C\nbyte 4\nincbin \"sprite.bin\"\ninclude \"loop.asm\"\n";
+
+        let mut assembler = Assembler::from(source).with_files(|path: &str| match path {
+            "sprite.bin" => Some(&[0x3C, 0x42][..]),
+            "loop.asm" => Some(&b"end: jmp end"[..]),
+            _ => None,
+        });
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        assert_eq!(bytes[..0x100], [0; 0x100]);
+        assert_eq!(
+            bytes[0x100..],
+            [
+                0x01, 0x02, 0x03, 0x00, 0x03, 0x00, 0x12, 0x34, 0xFF, 0xFF, 0xFF, 0x00, 0x04, 0x3C,
+                0x42, 0x13, 0x0F
+            ]
+        );
+        assert_eq!(assembler.symbols().get("end"), Some(&0x30F));
+
+        // Included code is on the line of its `include`.
+        assert_eq!(assembler.source_map().entries(), &[(0x30F, 9)]);
+        assert!(assembler.errors().is_empty());
+    }
+
+    #[test]
+    fn test_directive_errors() {
+        let source = "cls\norg 0x100\nincbin \"missing.bin\"\ninclude \"self.asm\"";
+
+        let mut assembler = Assembler::from(source).with_files(|path: &str| match path {
+            "self.asm" => Some(&b"include \"self.asm\""[..]),
+            _ => None,
+        });
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        assert_eq!(bytes, [0x00, 0xE0]);
+
+        let errors: Vec<&ParserError> = assembler.errors().iter().map(Diagnostic::error).collect();
+        assert!(matches!(
+            errors[..],
+            [
+                ParserError::OrgBackwards(0x100),
+                ParserError::UnreadableFile("missing.bin"),
+                ParserError::NestedTooDeep("self.asm")
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_org_padding() {
+        let source = "jmp start\norg 0x300\nstart: ld i, sprite\ndrw v0, v0, 1\nend: jmp end\n\
+                      sprite: db 0x80";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        assert_eq!(bytes.len(), 0x107);
+        assert_eq!(assembler.source_map().entries()[1], (0x300, 3));
+        assert!(assembler.errors().is_empty());
+
+        // Loaded at 0x200 the program reaches `start` and draws its sprite.
+        let mut interpreter = Interpreter::default();
+        interpreter.load(&bytes).unwrap();
+
+        for _ in 0..4 {
+            interpreter.cycle().unwrap();
+        }
+
+        assert_eq!(interpreter.screen().pixel(0, 0), 1);
+    }
+
+    #[test]
+    fn test_expressions() {
+        let source = "SPEED equ 4\ndefine COUNT SPEED * 2 + 1\n\
+                      start: db SPEED, -1, ~0x0F & 0xFF, (1 + 2) * 3, 1 << 4 | 1, 17 % 5\n\
+                      dw hi(start + 0x102), lo(data), data - start\nfill COUNT / 4\n\
+                      se v0, COUNT - 0x10 + 0x10\ndata:";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        assert_eq!(
+            bytes,
+            [
+                0x04, 0xFF, 0xF0, 0x09, 0x11, 0x02, 0x00, 0x03, 0x00, 0x10, 0x00, 0x10, 0x00, 0x00,
+                0x30, 0x09
+            ]
+        );
+        assert_eq!(assembler.symbols().get("COUNT"), Some(&9));
+        assert!(assembler.errors().is_empty());
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        let source = "db 0x100\ndb -129\njmp 0x1000\nscd 16\ndb 1 / 0\nX equ 1\nX equ 2\ndb 255";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        // Everything is still assembled, cut down to the width of its operand.
+        assert_eq!(bytes, [0x00, 0x7F, 0x10, 0x00, 0x00, 0xC0, 0x00, 0xFF]);
+
+        let errors: Vec<&ParserError> = assembler.errors().iter().map(Diagnostic::error).collect();
+        assert!(matches!(
+            errors[..],
+            [
+                ParserError::OutOfRange(0x100, 8),
+                ParserError::OutOfRange(-129, 8),
+                ParserError::OutOfRange(0x1000, 12),
+                ParserError::OutOfRange(16, 4),
+                ParserError::DivisionByZero,
+                ParserError::DuplicateSymbol("X"),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_operands() {
+        let source = "ld v1, 0x20\nld v1, v2\nld i, sprite\nld i, long sprite\nld v3, dt\n\
+                      ld v3, k\nld dt, v4\nld st, v4\nld f, v5\nld hf, v5\nld b, v6\n\
+                      ld [i], v7\nld v7, [i]\nld r, v8\nld v8, r\nadd v1, 1\nadd v1, v2\n\
+                      add i, v9\njmp v0, sprite\nsprite:";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        assert_eq!(
+            bytes,
+            [
+                0x61, 0x20, 0x81, 0x20, 0xA2, 0x28, 0xF0, 0x00, 0x02, 0x28, 0xF3, 0x07, 0xF3, 0x0A,
+                0xF4, 0x15, 0xF4, 0x18, 0xF5, 0x29, 0xF5, 0x30, 0xF6, 0x33, 0xF7, 0x55, 0xF7, 0x65,
+                0xF8, 0x75, 0xF8, 0x85, 0x71, 0x01, 0x81, 0x24, 0xF9, 0x1E, 0xB2, 0x28
+            ]
+        );
+        assert!(assembler.errors().is_empty());
+
+        let mut assembler = Assembler::from("ld dt, 5");
+        assert_eq!(assembler.by_ref().count(), 0);
+        assert!(matches!(
+            assembler
+                .errors()
+                .iter()
+                .map(Diagnostic::error)
+                .collect::<Vec<_>>()[..],
+            [ParserError::ExpectedRegister(_)]
+        ));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let source = "cls\nld v0 0x10\njmp nowhere\ncls\nadd v1,";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        // Only the rest of a line with an error is skipped.
+        assert_eq!(bytes, [0x00, 0xE0, 0x10, 0x00, 0x00, 0xE0]);
+
+        let errors: Vec<_> = assembler
+            .errors()
+            .iter()
+            .map(|diagnostic| (diagnostic.line(), diagnostic.column(), diagnostic.span()))
+            .collect();
+        assert_eq!(errors, [(2, 7, 10..14), (3, 5, 19..26), (5, 1, 31..38)]);
+        assert!(matches!(
+            assembler.errors()[0].error(),
+            ParserError::Expected(_, Token::Number(0x10))
+        ));
+        assert_eq!(
+            assembler.errors()[0].to_string(),
+            "2:7: Expected `,`, but found `16`"
+        );
+        assert_eq!(
+            assembler.errors()[2].to_string(),
+            "5:1: The line ended in the middle of a statement"
+        );
+
+        let diagnostics = Assembler::from(source).assemble().unwrap_err();
+        assert_eq!(diagnostics.len(), 3);
+
+        let program = Assembler::from("start: jmp start").assemble().unwrap();
+        assert_eq!(program.bytes(), [0x12, 0x00]);
+        assert_eq!(program.symbols().get("start"), Some(&0x200));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let source = "jmp\nld v0, 300\nse v1,\n  drw v0, v1\ncls\nfoo\n";
+
+        let mut assembler = Assembler::from(source);
+        let bytes: Vec<u8> = assembler.by_ref().collect();
+
+        // Statements end with their line, the next one is still assembled.
+        assert_eq!(bytes, [0x60, 0x2C, 0x00, 0xE0]);
+
+        let errors: Vec<_> = assembler
+            .errors()
+            .iter()
+            .map(|diagnostic| (diagnostic.line(), diagnostic.column(), diagnostic.span()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (1, 1, 0..3),
+                (2, 8, 11..14),
+                (3, 1, 15..21),
+                (4, 3, 24..34),
+                (6, 1, 39..42)
+            ]
+        );
+        assert!(matches!(
+            assembler.errors()[1].error(),
+            ParserError::OutOfRange(300, 8)
+        ));
+    }
+
+    #[test]
+    fn test_reserved_words() {
+        let source = "f: cls\nexit equ 1\njmp save\nv0:\nk: ld v0, k\nfill:";
+
+        let mut assembler = Assembler::from(source);
+        assert_eq!(assembler.by_ref().count(), 0);
+
+        let errors: Vec<&ParserError> = assembler.errors().iter().map(Diagnostic::error).collect();
+        assert!(matches!(
+            errors[..],
+            [
+                ParserError::Reserved(Token::Keyword(Keyword::F)),
+                ParserError::Reserved(Token::Mnemonic(Mnemonic::Exit)),
+                ParserError::Reserved(Token::Mnemonic(Mnemonic::Save)),
+                ParserError::Reserved(Token::Register(Register::V0)),
+                ParserError::Reserved(Token::Keyword(Keyword::K)),
+                ParserError::Reserved(Token::Directive(Directive::Fill)),
+            ]
+        ));
+
+        // The same words are fine as part of a longer name.
+        let program = Assembler::from("f0: ld v0, k\nexit_1: jmp f0").assemble();
+        assert_eq!(program.unwrap().bytes(), [0xF0, 0x0A, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn test_forward_symbols() {
+        let program = Assembler::from("fill COUNT\njmp end\nCOUNT equ 4\nend: cls")
+            .assemble()
+            .unwrap();
+
+        assert_eq!(
+            program.bytes(),
+            [0x00, 0x00, 0x00, 0x00, 0x12, 0x06, 0x00, 0xE0]
+        );
+        assert_eq!(program.symbols().get("end"), Some(&0x206));
+
+        // The size of the fill flips with the address of the label after it.
+        let diagnostics = Assembler::from("fill (end & 2) ^ 2\nend: cls")
+            .assemble()
+            .unwrap_err();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(
+            diagnostics[0].error(),
+            ParserError::UnstableSymbol("end")
+        ));
+        assert_eq!(diagnostics[0].line(), 2);
     }
 }
@@ -0,0 +1,108 @@
+use alloc::string::{String, ToString};
+use chip_lexer::span::position;
+use core::fmt::{Display, Formatter, Write};
+use core::ops::Range;
+
+use crate::parser::ParserError;
+
+// An error together with where in the source it is.
+#[derive(Debug, Clone)]
+pub struct Diagnostic<'d> {
+    error: ParserError<'d>,
+    span: Range<usize>,
+    // One based, the column counts characters.
+    line: usize,
+    column: usize,
+}
+
+impl<'d> Diagnostic<'d> {
+    pub fn new(error: ParserError<'d>, span: Range<usize>, source: &str) -> Self {
+        let (line, column) = position(source, span.start);
+
+        Self {
+            error,
+            span,
+            line,
+            column,
+        }
+    }
+
+    pub fn error(&self) -> &ParserError<'d> {
+        &self.error
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    // The error followed by its line of `source`, with the span underlined.
+    pub fn render(&self, source: &str) -> String {
+        let line_start = source[..self.span.start]
+            .rfind('\n')
+            .map_or(0, |pos| pos + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |pos| line_start + pos);
+
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        // Tabs are kept so the carets line up however wide they are shown.
+        let indent: String = source[line_start..self.span.start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let end = self.span.end.min(line_end).max(self.span.start);
+        let carets = "^".repeat(source[self.span.start..end].chars().count().max(1));
+
+        let mut rendered = String::new();
+
+        let _ = writeln!(rendered, "error: {}", self.error);
+        let _ = writeln!(rendered, "{} --> {}:{}", gutter, self.line, self.column);
+        let _ = writeln!(rendered, "{} |", gutter);
+        let _ = writeln!(rendered, "{} | {}", number, text);
+        let _ = write!(rendered, "{} | {}{}", gutter, indent, carets);
+
+        rendered
+    }
+}
+
+impl<'d> Display for Diagnostic<'d> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use crate::parser::ParserError;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_render() {
+        let source = "cls\n\tjmp nowhere ; loop\nret";
+        let diagnostic = Diagnostic::new(ParserError::UndefinedSymbol("nowhere"), 9..16, source);
+
+        assert_eq!((diagnostic.line(), diagnostic.column()), (2, 6));
+        assert_eq!(
+            diagnostic.to_string(),
+            "2:6: The symbol nowhere isn't defined"
+        );
+        assert_eq!(
+            diagnostic.render(source),
+            "error: The symbol nowhere isn't defined\n  --> 2:6\n  |\n\
+             2 | \tjmp nowhere ; loop\n  | \t    ^^^^^^^"
+        );
+    }
+}
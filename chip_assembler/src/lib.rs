@@ -1,5 +1,8 @@
 #![no_std]
-#![feature(error_in_core)]
+
+extern crate alloc;
 
 pub mod assembler;
+pub mod diagnostic;
 pub mod parser;
+pub mod source_map;
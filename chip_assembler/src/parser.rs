@@ -1,28 +1,274 @@
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
 use chip_lexer::lexer::Lexer;
-use chip_lexer::token::{Delimeter, Mnemonic, Register, Token};
-use core::fmt::{Display, Formatter};
-use core::iter::Peekable;
+use chip_lexer::span::Spanned;
+use chip_lexer::token::{Delimeter, Directive, Keyword, Mnemonic, Operator, Register, Token};
 use core::error::Error;
 use core::fmt::Debug;
+use core::fmt::{Display, Formatter};
+use core::ops::Range;
+
+use crate::diagnostic::Diagnostic;
+
+// Deepest `include` nesting, which also stops a file from including itself forever.
+const MAX_INCLUDE_DEPTH: usize = 16;
 
+// Binary operators from the loosest to the tightest binding.
+const PRECEDENCE: [&[Operator]; 6] = [
+    &[Operator::Pipe],
+    &[Operator::Caret],
+    &[Operator::Ampersand],
+    &[Operator::ShiftLeft, Operator::ShiftRight],
+    &[Operator::Plus, Operator::Minus],
+    &[Operator::Star, Operator::Slash, Operator::Percent],
+];
+
+// A token lexed ahead.
+type Peeked<'p> = Option<Spanned<'p, Token<'p>>>;
+
+#[derive(Clone)]
 pub struct Parser<'p> {
-    lexer: Peekable<Lexer<'p>>,
+    // The outermost file, which every span points into.
+    source: &'p str,
+    lexer: Lexer<'p>,
+    peeked: Peeked<'p>,
+    // Files the current one is included from, outermost first.
+    includes: Vec<(Lexer<'p>, Peeked<'p>)>,
+    // Source line of the last parsed instruction.
+    line: usize,
+    // Span of the last token, tokens of included files have the span of their `include`.
+    span: Range<usize>,
+    // Where the statement being parsed starts.
+    start: usize,
+    // Line and include depth of the statement being parsed, which ends with its line.
+    statement: Option<(usize, usize)>,
+    // Values of the labels and constants, complete once the first pass over the source is done.
+    symbols: BTreeMap<&'p str, u16>,
+    // Symbols defined by this pass, to find the ones defined twice.
+    defined: BTreeSet<&'p str>,
+    // Until `symbols` is complete, undefined symbols are 0 and values go unchecked.
+    resolving: bool,
+    errors: Vec<Diagnostic<'p>>,
 }
 
 impl<'p> Parser<'p> {
-    fn parse_instruction(&mut self) -> Result<Instruction, ParserError<'p>> {
-        match self.parse_mnemonic()? {
+    // Resolves symbols with the complete `symbols` of a previous pass.
+    pub fn with_symbols(mut self, symbols: BTreeMap<&'p str, u16>) -> Self {
+        self.symbols = symbols;
+        self.resolving = true;
+        self
+    }
+
+    pub fn symbols(&self) -> &BTreeMap<&'p str, u16> {
+        &self.symbols
+    }
+
+    // Defines a label or constant, each name only once.
+    pub fn define(&mut self, name: &'p str, value: u16) -> Result<(), ParserError<'p>> {
+        if !self.defined.insert(name) {
+            return Err(ParserError::DuplicateSymbol(name));
+        }
+
+        self.symbols.insert(name, value);
+
+        Ok(())
+    }
+
+    // Line in the outermost file, included code is on the line of its `include`.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // Continues with `source` until it ends, then with the rest of the current file.
+    pub fn include(&mut self, path: &'p str, source: &'p str) -> Result<(), ParserError<'p>> {
+        if self.includes.len() >= MAX_INCLUDE_DEPTH {
+            return Err(ParserError::NestedTooDeep(path));
+        }
+
+        let outer = core::mem::replace(&mut self.lexer, Lexer::from(source));
+        self.includes.push((outer, self.peeked.take()));
+
+        Ok(())
+    }
+
+    // Bytes of the last parsed statement in the outermost file.
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.span.end.max(self.start)
+    }
+
+    // Reports an error in the last parsed statement, found after parsing it.
+    pub fn report(&mut self, error: ParserError<'p>) {
+        self.error(error, self.span());
+    }
+
+    // Errors found so far, in the order of the source.
+    pub fn errors(&self) -> &[Diagnostic<'p>] {
+        &self.errors
+    }
+
+    fn error(&mut self, error: ParserError<'p>, span: Range<usize>) {
+        self.errors.push(Diagnostic::new(error, span, self.source));
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement<'p>, ParserError<'p>> {
+        match self.next_token() {
+            Some(Token::Identifier(name)) => match self.next_token() {
+                Some(Token::Delimeter(Delimeter::Colon)) => Ok(Statement::Label(name)),
+                Some(Token::Directive(Directive::Equ)) => self.parse_constant(name),
+                Some(token) => Err(ParserError::Expected(
+                    Token::Delimeter(Delimeter::Colon),
+                    token,
+                )),
+                None => Err(ParserError::InputEnded(Token::Delimeter(Delimeter::Colon))),
+            },
+            Some(token) if is_reserved(&token) && self.peek_symbol_definition() => {
+                Err(ParserError::Reserved(token))
+            }
+            Some(Token::Directive(directive)) => self.parse_directive(directive),
+            Some(Token::Mnemonic(mnemonic)) => {
+                self.parse_instruction(mnemonic).map(Statement::Instruction)
+            }
+            Some(token) => Err(ParserError::ExpectedMnemonic(token)),
+            None => Err(ParserError::UnexpectedEnd),
+        }
+    }
+
+    // Whether the next token makes the last one the name of a label or constant.
+    fn peek_symbol_definition(&mut self) -> bool {
+        matches!(
+            self.peek(),
+            Some(Token::Delimeter(Delimeter::Colon) | Token::Directive(Directive::Equ))
+        )
+    }
+
+    fn parse_directive(&mut self, directive: Directive) -> Result<Statement<'p>, ParserError<'p>> {
+        match directive {
+            Directive::Equ => Err(ParserError::ExpectedIdentifier(Token::Directive(directive))),
+            Directive::Define => {
+                let name = self.parse_identifier()?;
+
+                self.parse_constant(name)
+            }
+            Directive::Org => Ok(Statement::Org(self.parse_number(16)?)),
+            Directive::Byte => Ok(Statement::Byte(self.parse_numbers(8)?)),
+            Directive::Word => Ok(Statement::Word(self.parse_numbers(16)?)),
+            Directive::Align => Ok(Statement::Align(self.parse_number(16)?)),
+            Directive::Fill => {
+                let count = self.parse_number(16)?;
+
+                let value = match self.peek() {
+                    Some(Token::Delimeter(Delimeter::Comma)) => {
+                        self.next_token();
+                        self.parse_number(8)?
+                    }
+                    _ => 0,
+                };
+
+                Ok(Statement::Fill(count, value))
+            }
+            Directive::Incbin => Ok(Statement::Incbin(self.parse_string()?)),
+            Directive::Include => Ok(Statement::Include(self.parse_string()?)),
+        }
+    }
+
+    fn parse_constant(&mut self, name: &'p str) -> Result<Statement<'p>, ParserError<'p>> {
+        let value = self.parse_number(16)?;
+
+        if let Err(err) = self.define(name, value) {
+            self.report(err);
+        }
+
+        Ok(Statement::Constant(name, value))
+    }
+
+    // The next token of the statement being parsed, none once its line ended.
+    fn peek(&mut self) -> Option<&Token<'p>> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex();
+        }
+
+        let position = (self.lexer.line(), self.includes.len());
+
+        if matches!(self.statement, Some(statement) if statement != position) {
+            return None;
+        }
+
+        self.peeked.as_ref().map(Spanned::value)
+    }
+
+    // Where the next token starts, the end of the input once there is none.
+    fn peek_start(&mut self) -> usize {
+        self.peek();
+
+        self.peeked
+            .as_ref()
+            .map_or(self.source.len(), Spanned::start)
+    }
+
+    fn next_token(&mut self) -> Option<Token<'p>> {
+        self.peek()?;
+
+        let token = self.peeked.take()?;
+
+        self.span = token.span();
+
+        Some(token.into_value())
+    }
+
+    fn lex(&mut self) -> Peeked<'p> {
+        loop {
+            if let Some(token) = self.lexer.next() {
+                return match self.includes.is_empty() {
+                    true => Some(token),
+                    false => Some(Spanned::new(
+                        token.into_value(),
+                        self.span.clone(),
+                        self.source,
+                    )),
+                };
+            }
+
+            // The included file ended, back to the one including it.
+            let (outer, peeked) = self.includes.pop()?;
+            self.lexer = outer;
+
+            if peeked.is_some() {
+                return peeked;
+            }
+        }
+    }
+
+    // Skips the rest of the line of the statement, to continue after an error.
+    fn skip_line(&mut self) {
+        while self.next_token().is_some() {}
+    }
+
+    fn parse_instruction(&mut self, mnemonic: Mnemonic) -> Result<Instruction, ParserError<'p>> {
+        match mnemonic {
             Mnemonic::Cls => Ok(Instruction::Cls),
             Mnemonic::Ret => Ok(Instruction::Ret),
             sys @ Mnemonic::Sys => Err(ParserError::Unsupported(sys)),
-            Mnemonic::Jmp => Ok(Instruction::JmpAddress(self.parse_number()?)),
-            Mnemonic::Call => Ok(Instruction::Call(self.parse_number()?)),
+            Mnemonic::Jmp => match self.peek() {
+                Some(&Token::Register(_)) => {
+                    // Only `V0` is an offset, `BXNN` is a SUPER-CHIP quirk and not an instruction.
+                    self.parse_token(Token::Register(Register::V0))?;
+                    self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                    Ok(Instruction::JmpRegAddress(
+                        Register::V0,
+                        self.parse_number(12)?,
+                    ))
+                }
+                _ => Ok(Instruction::JmpAddress(self.parse_number(12)?)),
+            },
+            Mnemonic::Call => Ok(Instruction::Call(self.parse_number(12)?)),
             Mnemonic::Se => {
                 let vx = self.parse_register()?;
 
                 self.parse_token(Token::Delimeter(Delimeter::Comma))?;
 
-                match self.parse_operand()? {
+                match self.parse_operand(8)? {
                     Operand::Register(vy) => Ok(Instruction::SeRegReg(vx, vy)),
                     Operand::Number(number) => Ok(Instruction::SeRegVal(vx, number)),
                 }
@@ -32,17 +278,29 @@ impl<'p> Parser<'p> {
 
                 self.parse_token(Token::Delimeter(Delimeter::Comma))?;
 
-                match self.parse_operand()? {
+                match self.parse_operand(8)? {
                     Operand::Register(vy) => Ok(Instruction::SneRegReg(vx, vy)),
                     Operand::Number(number) => Ok(Instruction::SneRegVal(vx, number)),
                 }
             }
-            Mnemonic::Ld => {
-                todo!()
-            }
-            Mnemonic::Add => {
-                todo!()
-            }
+            Mnemonic::Ld => self.parse_ld(),
+            Mnemonic::Add => match self.next_token() {
+                Some(Token::Register(vx)) => {
+                    self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                    match self.parse_operand(8)? {
+                        Operand::Register(vy) => Ok(Instruction::AddRegReg(vx, vy)),
+                        Operand::Number(number) => Ok(Instruction::AddRegVal(vx, number)),
+                    }
+                }
+                Some(Token::Keyword(Keyword::I)) => {
+                    self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                    Ok(Instruction::AddIndexReg(0, self.parse_register()?))
+                }
+                Some(token) => Err(ParserError::ExpectedOperand(token)),
+                None => Err(ParserError::UnexpectedEnd),
+            },
             Mnemonic::Or => {
                 let vx = self.parse_register()?;
 
@@ -88,82 +346,399 @@ impl<'p> Parser<'p> {
 
                 Ok(Instruction::Shr(vx, vy))
             }
-            instruction => panic!("{:#?}", instruction),
+            Mnemonic::Subn => {
+                let vx = self.parse_register()?;
+
+                self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                let vy = self.parse_register()?;
+
+                Ok(Instruction::Subn(vx, vy))
+            }
+            Mnemonic::Shl => {
+                let vx = self.parse_register()?;
+
+                self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                let vy = self.parse_register()?;
+
+                Ok(Instruction::Shl(vx, vy))
+            }
+            Mnemonic::Rnd => {
+                let vx = self.parse_register()?;
+
+                self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                Ok(Instruction::Rnd(vx, self.parse_number(8)?))
+            }
+            Mnemonic::Drw => {
+                let vx = self.parse_register()?;
+
+                self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                let vy = self.parse_register()?;
+
+                self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                Ok(Instruction::Drw(vx, vy, self.parse_number(4)?))
+            }
+            Mnemonic::Skp => Ok(Instruction::Skp(self.parse_register()?)),
+            Mnemonic::Sknp => Ok(Instruction::Skpn(self.parse_register()?)),
+            Mnemonic::Scd => Ok(Instruction::Scd(self.parse_number(4)?)),
+            Mnemonic::Scr => Ok(Instruction::Scr),
+            Mnemonic::Scl => Ok(Instruction::Scl),
+            Mnemonic::Exit => Ok(Instruction::Exit),
+            Mnemonic::Low => Ok(Instruction::Low),
+            Mnemonic::High => Ok(Instruction::High),
+            Mnemonic::Save => {
+                let vx = self.parse_register()?;
+
+                self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                let vy = self.parse_register()?;
+
+                Ok(Instruction::Save(vx, vy))
+            }
+            Mnemonic::Load => {
+                let vx = self.parse_register()?;
+
+                self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+                let vy = self.parse_register()?;
+
+                Ok(Instruction::Load(vx, vy))
+            }
+            Mnemonic::Plane => Ok(Instruction::Plane(self.parse_number(4)?)),
+            Mnemonic::Audio => Ok(Instruction::Audio),
+            Mnemonic::Pitch => Ok(Instruction::Pitch(self.parse_register()?)),
+        }
+    }
+
+    fn parse_ld(&mut self) -> Result<Instruction, ParserError<'p>> {
+        let destination = match self.next_token() {
+            Some(token) => token,
+            None => return Err(ParserError::UnexpectedEnd),
+        };
+
+        if destination == Token::Delimeter(Delimeter::LeftBracket) {
+            self.parse_token(Token::Keyword(Keyword::I))?;
+            self.parse_token(Token::Delimeter(Delimeter::RightBracket))?;
+        }
+
+        self.parse_token(Token::Delimeter(Delimeter::Comma))?;
+
+        match destination {
+            Token::Register(vx) => match self.peek() {
+                Some(&Token::Keyword(Keyword::Dt)) => {
+                    self.next_token();
+                    Ok(Instruction::LdRegDelay(vx, 0))
+                }
+                Some(&Token::Keyword(Keyword::K)) => {
+                    self.next_token();
+                    Ok(Instruction::LdRegKey(vx, 0))
+                }
+                Some(&Token::Keyword(Keyword::R)) => {
+                    self.next_token();
+                    Ok(Instruction::LdRegR(vx))
+                }
+                Some(&Token::Delimeter(Delimeter::LeftBracket)) => {
+                    self.next_token();
+                    self.parse_token(Token::Keyword(Keyword::I))?;
+                    self.parse_token(Token::Delimeter(Delimeter::RightBracket))?;
+
+                    Ok(Instruction::LdRegMemIndex(vx))
+                }
+                _ => match self.parse_operand(8)? {
+                    Operand::Register(vy) => Ok(Instruction::LdRegReg(vx, vy)),
+                    Operand::Number(number) => Ok(Instruction::LdRegVal(vx, number)),
+                },
+            },
+            Token::Keyword(Keyword::I) => match self.peek() {
+                Some(&Token::Keyword(Keyword::Long)) => {
+                    self.next_token();
+                    Ok(Instruction::LdIndexLong(self.parse_number(16)?))
+                }
+                _ => Ok(Instruction::LdIndex(0, self.parse_number(12)?)),
+            },
+            Token::Keyword(Keyword::Dt) => Ok(Instruction::LdDelayReg(0, self.parse_register()?)),
+            Token::Keyword(Keyword::St) => Ok(Instruction::LdSoundReg(0, self.parse_register()?)),
+            Token::Keyword(Keyword::F) => Ok(Instruction::LdFReg(self.parse_register()?)),
+            Token::Keyword(Keyword::Hf) => Ok(Instruction::LdHfReg(self.parse_register()?)),
+            Token::Keyword(Keyword::B) => Ok(Instruction::LdBReg(self.parse_register()?)),
+            Token::Keyword(Keyword::R) => Ok(Instruction::LdRReg(self.parse_register()?)),
+            Token::Delimeter(Delimeter::LeftBracket) => {
+                Ok(Instruction::LdMemIndexReg(self.parse_register()?))
+            }
+            token => Err(ParserError::ExpectedOperand(token)),
         }
     }
 
     fn parse_token(&mut self, expected: Token<'p>) -> Result<Token<'p>, ParserError<'p>> {
-        match self.lexer.next() {
+        match self.next_token() {
             Some(token) if token == expected => Ok(token),
             Some(token) => Err(ParserError::Expected(expected, token)),
             None => Err(ParserError::InputEnded(expected)),
         }
     }
 
-    fn parse_mnemonic(&mut self) -> Result<Mnemonic, ParserError<'p>> {
-        match self.lexer.next() {
-            Some(Token::Mnemonic(mnemonic)) => Ok(mnemonic),
-            Some(token) => Err(ParserError::ExpectedMnemonic(token)),
-            None => todo!("InputEnded error"),
-        }
-    }
-
     fn parse_register(&mut self) -> Result<Register, ParserError<'p>> {
-        match self.lexer.next() {
+        match self.next_token() {
             Some(Token::Register(register)) => Ok(register),
             Some(token) => Err(ParserError::ExpectedRegister(token)),
-            None => todo!("InputEnded error"),
+            None => Err(ParserError::UnexpectedEnd),
+        }
+    }
+
+    // Constant expression for an operand of `bits` bits, negative values are two's complement.
+    // Values that don't fit are reported and cut off, keeping the layout of the program intact.
+    fn parse_number(&mut self, bits: u32) -> Result<u16, ParserError<'p>> {
+        let start = self.peek_start();
+        let value = self.parse_expression(0)?;
+        let max = (1 << bits) - 1;
+
+        if self.resolving && !(-(1 << (bits - 1))..=max).contains(&value) {
+            self.error(ParserError::OutOfRange(value, bits), start..self.span.end);
+        }
+
+        Ok((value & max) as u16)
+    }
+
+    fn parse_numbers(&mut self, bits: u32) -> Result<Vec<u16>, ParserError<'p>> {
+        let mut numbers = vec![self.parse_number(bits)?];
+
+        while let Some(Token::Delimeter(Delimeter::Comma)) = self.peek() {
+            self.next_token();
+            numbers.push(self.parse_number(bits)?);
+        }
+
+        Ok(numbers)
+    }
+
+    // Binary operators of `level` and everything binding tighter than them.
+    fn parse_expression(&mut self, level: usize) -> Result<i64, ParserError<'p>> {
+        let operators = match PRECEDENCE.get(level) {
+            Some(operators) => *operators,
+            None => return self.parse_unary(),
+        };
+
+        let mut lhs = self.parse_expression(level + 1)?;
+
+        while let Some(&Token::Operator(operator)) = self.peek() {
+            if !operators.contains(&operator) {
+                break;
+            }
+
+            self.next_token();
+
+            let rhs = self.parse_expression(level + 1)?;
+            lhs = self.apply(operator, lhs, rhs);
         }
+
+        Ok(lhs)
     }
 
-    fn parse_number(&mut self) -> Result<u16, ParserError<'p>> {
-        match self.lexer.next() {
-            Some(Token::Number(number)) => Ok(number),
+    fn parse_unary(&mut self) -> Result<i64, ParserError<'p>> {
+        match self.peek() {
+            Some(Token::Operator(Operator::Minus)) => {
+                self.next_token();
+                Ok(self.parse_unary()?.wrapping_neg())
+            }
+            Some(Token::Operator(Operator::Tilde)) => {
+                self.next_token();
+                Ok(!self.parse_unary()?)
+            }
+            Some(Token::Operator(Operator::Plus)) => {
+                self.next_token();
+                self.parse_unary()
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<i64, ParserError<'p>> {
+        match self.next_token() {
+            Some(Token::Number(number)) => Ok(number as i64),
+            Some(Token::Identifier(name @ ("hi" | "lo")))
+                if self.peek() == Some(&Token::Delimeter(Delimeter::LeftParen)) =>
+            {
+                let value = self.parse_primary()?;
+
+                match name {
+                    "hi" => Ok(value >> 8 & 0xFF),
+                    _ => Ok(value & 0xFF),
+                }
+            }
+            Some(Token::Identifier(name)) => Ok(self.resolve(name)),
+            Some(Token::Delimeter(Delimeter::LeftParen)) => {
+                let value = self.parse_expression(0)?;
+                self.parse_token(Token::Delimeter(Delimeter::RightParen))?;
+
+                Ok(value)
+            }
+            Some(token @ (Token::Mnemonic(_) | Token::Directive(_) | Token::Keyword(_))) => {
+                Err(ParserError::Reserved(token))
+            }
             Some(token) => Err(ParserError::ExpectedNumber(token)),
-            None => todo!("InputEnded error"),
+            None => Err(ParserError::UnexpectedEnd),
+        }
+    }
+
+    fn apply(&mut self, operator: Operator, lhs: i64, rhs: i64) -> i64 {
+        match operator {
+            Operator::Plus => lhs.wrapping_add(rhs),
+            Operator::Minus => lhs.wrapping_sub(rhs),
+            Operator::Star => lhs.wrapping_mul(rhs),
+            Operator::Slash | Operator::Percent if rhs == 0 => {
+                if self.resolving {
+                    self.error(ParserError::DivisionByZero, self.span.clone());
+                }
+
+                0
+            }
+            Operator::Slash => lhs.wrapping_div(rhs),
+            Operator::Percent => lhs.wrapping_rem(rhs),
+            Operator::Ampersand => lhs & rhs,
+            Operator::Pipe => lhs | rhs,
+            Operator::Caret => lhs ^ rhs,
+            Operator::ShiftLeft => u32::try_from(rhs)
+                .ok()
+                .and_then(|rhs| lhs.checked_shl(rhs))
+                .unwrap_or(0),
+            Operator::ShiftRight => u32::try_from(rhs)
+                .ok()
+                .and_then(|rhs| lhs.checked_shr(rhs))
+                .unwrap_or(0),
+            Operator::Tilde => unreachable!("~ is unary"),
         }
     }
 
-    fn parse_operand(&mut self) -> Result<Operand, ParserError<'p>> {
-        match self.lexer.next() {
-            Some(Token::Register(register)) => Ok(Operand::Register(register)),
-            Some(Token::Number(number)) => Ok(Operand::Number(number)),
-            Some(_token) => todo!("Expected operand ParserError"),
-            None => todo!("InputEnded error"),
+    fn parse_identifier(&mut self) -> Result<&'p str, ParserError<'p>> {
+        match self.next_token() {
+            Some(Token::Identifier(name)) => Ok(name),
+            Some(token) => Err(ParserError::ExpectedIdentifier(token)),
+            None => Err(ParserError::UnexpectedEnd),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<&'p str, ParserError<'p>> {
+        match self.next_token() {
+            Some(Token::String(string)) => Ok(string),
+            Some(token) => Err(ParserError::ExpectedString(token)),
+            None => Err(ParserError::UnexpectedEnd),
+        }
+    }
+
+    fn parse_operand(&mut self, bits: u32) -> Result<Operand, ParserError<'p>> {
+        match self.peek() {
+            Some(&Token::Register(register)) => {
+                self.next_token();
+                Ok(Operand::Register(register))
+            }
+            _ => Ok(Operand::Number(self.parse_number(bits)?)),
+        }
+    }
+
+    // Undefined symbols are reported but still resolve to 0, so the addresses of everything
+    // after them match the first pass.
+    fn resolve(&mut self, name: &'p str) -> i64 {
+        match self.symbols.get(name) {
+            Some(&value) => value as i64,
+            None => {
+                if self.resolving {
+                    self.error(ParserError::UndefinedSymbol(name), self.span.clone());
+                }
+
+                0
+            }
         }
     }
 }
 
 impl<'p> Iterator for Parser<'p> {
-    type Item = Instruction;
+    type Item = Statement<'p>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.peek().is_some() {
+            self.line = match self.includes.first() {
+                Some((outermost, _)) => outermost.line(),
+                None => self.lexer.line(),
+            };
+            self.start = self.peek_start();
+            self.statement = Some((self.lexer.line(), self.includes.len()));
+
+            let result = self.parse_statement();
+
+            if let Err(err) = &result {
+                // A statement missing something is underlined as a whole.
+                let span = match err {
+                    ParserError::InputEnded(_) | ParserError::UnexpectedEnd => self.span(),
+                    _ => self.span.clone(),
+                };
+
+                self.error(err.clone(), span);
+                self.skip_line();
+            }
+
+            self.statement = None;
 
-    fn next(&mut self) -> Option<Self::Item> {        
-        while let Some(_) = self.lexer.peek() {
-            if let Ok(instruction) = self.parse_instruction() {
-                return Some(instruction);
-            } else {
-                // Consume tokens until a valid instruction or end of input
-                self.lexer.next();
+            if let Ok(statement) = result {
+                return Some(statement);
             }
         }
+
         None
     }
 }
 
+// Mnemonics, directives, registers and the operands of `ld` and `add` are lexed as such wherever
+// they are, so they can't name labels or constants.
+fn is_reserved(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Mnemonic(_) | Token::Directive(_) | Token::Register(_) | Token::Keyword(_)
+    )
+}
+
 impl<'p> From<Lexer<'p>> for Parser<'p> {
     fn from(lexer: Lexer<'p>) -> Self {
         Self {
-            lexer: lexer.peekable(),
+            source: lexer.input(),
+            lexer,
+            peeked: None,
+            includes: Vec::new(),
+            line: 1,
+            span: 0..0,
+            start: 0,
+            statement: None,
+            symbols: BTreeMap::new(),
+            defined: BTreeSet::new(),
+            resolving: false,
+            errors: Vec::new(),
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement<'s> {
+    Label(&'s str),
+    Constant(&'s str, u16),
+    Instruction(Instruction),
+    Org(u16),
+    Byte(Vec<u16>),
+    Word(Vec<u16>),
+    Align(u16),
+    // Count and value of the bytes.
+    Fill(u16, u16),
+    Incbin(&'s str),
+    Include(&'s str),
+}
+
 pub enum Operand {
     Register(Register),
     Number(u16),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     Cls,
     Ret,
@@ -199,32 +774,91 @@ pub enum Instruction {
     LdBReg(Register),
     LdMemIndexReg(Register),
     LdRegMemIndex(Register),
+    Scd(u16),
+    Scr,
+    Scl,
+    Exit,
+    Low,
+    High,
+    LdHfReg(Register),
+    LdRReg(Register),
+    LdRegR(Register),
+    LdIndexLong(u16),
+    Save(Register, Register),
+    Load(Register, Register),
+    Plane(u16),
+    Audio,
+    Pitch(Register),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParserError<'t> {
     Expected(Token<'t>, Token<'t>),
     ExpectedMnemonic(Token<'t>),
     ExpectedRegister(Token<'t>),
+    ExpectedOperand(Token<'t>),
     ExpectedNumber(Token<'t>),
+    ExpectedString(Token<'t>),
+    ExpectedIdentifier(Token<'t>),
     InputEnded(Token<'t>),
+    UnexpectedEnd,
     Unsupported(Mnemonic),
+    UndefinedSymbol(&'t str),
+    DuplicateSymbol(&'t str),
+    OutOfRange(i64, u32),
+    DivisionByZero,
+    UnreadableFile(&'t str),
+    NestedTooDeep(&'t str),
+    OrgBackwards(u16),
+    UnstableSymbol(&'t str),
+    Reserved(Token<'t>),
 }
 
 impl<'t> Display for ParserError<'t> {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Expected(expected, found) => {
-                write!(f, "Expected {:?}, but found {:?}", expected, found)
+                write!(f, "Expected `{}`, but found `{}`", expected, found)
             }
             Self::ExpectedMnemonic(found) => {
-                writeln!(f, "Expected mnemonic, but found {:?}", found)
+                write!(f, "Expected mnemonic, but found `{}`", found)
+            }
+            Self::ExpectedRegister(found) => write!(f, "Expected register, but found `{}`", found),
+            Self::ExpectedOperand(found) => write!(f, "Expected operand, but found `{}`", found),
+            Self::ExpectedNumber(found) => write!(f, "Expected number, but found `{}`", found),
+            Self::ExpectedString(found) => write!(f, "Expected string, but found `{}`", found),
+            Self::ExpectedIdentifier(found) => {
+                write!(f, "Expected identifier, but found `{}`", found)
             }
-            Self::ExpectedRegister(found) => write!(f, "Expected register, but found {:?}", found),
-            Self::ExpectedNumber(found) => write!(f, "Expected number, but found {:?}", found),
-            Self::InputEnded(token) => write!(f, "Expected {:?}, but the input has ended", token),
+            Self::InputEnded(token) => write!(f, "Expected `{}`, but the line has ended", token),
+            Self::UnexpectedEnd => write!(f, "The line ended in the middle of a statement"),
             Self::Unsupported(mnemonic) => {
-                write!(f, "The instruction {:?} isn't supported", mnemonic)
+                write!(f, "The instruction `{}` isn't supported", mnemonic)
+            }
+            Self::UndefinedSymbol(name) => write!(f, "The symbol {} isn't defined", name),
+            Self::DuplicateSymbol(name) => write!(f, "The symbol {} is already defined", name),
+            Self::OutOfRange(value, bits) => write!(f, "{} doesn't fit in {} bits", value, bits),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::UnreadableFile(path) => write!(f, "The file {} can't be read", path),
+            Self::NestedTooDeep(path) => {
+                write!(
+                    f,
+                    "Including {} nests more than {} files deep",
+                    path, MAX_INCLUDE_DEPTH
+                )
+            }
+            Self::OrgBackwards(address) => {
+                write!(f, "org {:#05X} is before the current address", address)
+            }
+            Self::UnstableSymbol(name) => {
+                write!(
+                    f,
+                    "The address of {} depends on itself and never settles",
+                    name
+                )
+            }
+            Self::Reserved(token) => {
+                write!(f, "`{}` is a reserved word and can't name a symbol", token)
             }
         }
     }
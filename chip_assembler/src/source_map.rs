@@ -0,0 +1,35 @@
+use alloc::vec::Vec;
+
+// Address of every assembled instruction and the source line it came from, in address order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    entries: Vec<(u16, usize)>,
+}
+
+impl SourceMap {
+    pub fn entries(&self) -> &[(u16, usize)] {
+        &self.entries
+    }
+
+    // Line of the instruction starting at `address`.
+    pub fn line(&self, address: u16) -> Option<usize> {
+        self.entries
+            .binary_search_by_key(&address, |&(address, _)| address)
+            .ok()
+            .map(|index| self.entries[index].1)
+    }
+
+    // Address of the first instruction on `line`, or on the closest line after it holding one,
+    // together with that line.
+    pub fn address(&self, line: usize) -> Option<(u16, usize)> {
+        self.entries
+            .iter()
+            .filter(|&&(_, other)| other >= line)
+            .min_by_key(|&&(address, other)| (other, address))
+            .copied()
+    }
+
+    pub(crate) fn push(&mut self, address: u16, line: usize) {
+        self.entries.push((address, line));
+    }
+}
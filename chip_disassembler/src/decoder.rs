@@ -0,0 +1,130 @@
+use chip_assembler::parser::Instruction;
+use chip_lexer::token::Register;
+
+// Decodes `opcode`, `next` is the word following it which `ld i, long NNNN | F000 NNNN` takes
+// its address from. Opcodes without an instruction are `None`.
+pub fn decode(opcode: u16, next: Option<u16>) -> Option<Instruction> {
+    let vx = register(opcode >> 8);
+    let vy = register(opcode >> 4);
+    let nnn = opcode & 0x0FFF;
+    let nn = opcode & 0x00FF;
+    let n = opcode & 0x000F;
+
+    let instruction = match opcode {
+        0x00E0 => Instruction::Cls,
+        0x00EE => Instruction::Ret,
+        0x00C0..=0x00CF => Instruction::Scd(n),
+        0x00FB => Instruction::Scr,
+        0x00FC => Instruction::Scl,
+        0x00FD => Instruction::Exit,
+        0x00FE => Instruction::Low,
+        0x00FF => Instruction::High,
+        0x1000..=0x1FFF => Instruction::JmpAddress(nnn),
+        0x2000..=0x2FFF => Instruction::Call(nnn),
+        0x3000..=0x3FFF => Instruction::SeRegVal(vx, nn),
+        0x4000..=0x4FFF => Instruction::SneRegVal(vx, nn),
+        0x5000..=0x5FFF => match n {
+            0x0 => Instruction::SeRegReg(vx, vy),
+            0x2 => Instruction::Save(vx, vy),
+            0x3 => Instruction::Load(vx, vy),
+            _ => return None,
+        },
+        0x6000..=0x6FFF => Instruction::LdRegVal(vx, nn),
+        0x7000..=0x7FFF => Instruction::AddRegVal(vx, nn),
+        0x8000..=0x8FFF => match n {
+            0x0 => Instruction::LdRegReg(vx, vy),
+            0x1 => Instruction::Or(vx, vy),
+            0x2 => Instruction::And(vx, vy),
+            0x3 => Instruction::Xor(vx, vy),
+            0x4 => Instruction::AddRegReg(vx, vy),
+            0x5 => Instruction::Sub(vx, vy),
+            0x6 => Instruction::Shr(vx, vy),
+            0x7 => Instruction::Subn(vx, vy),
+            0xE => Instruction::Shl(vx, vy),
+            _ => return None,
+        },
+        0x9000..=0x9FFF if n == 0 => Instruction::SneRegReg(vx, vy),
+        0xA000..=0xAFFF => Instruction::LdIndex(0, nnn),
+        0xB000..=0xBFFF => Instruction::JmpRegAddress(Register::V0, nnn),
+        0xC000..=0xCFFF => Instruction::Rnd(vx, nn),
+        0xD000..=0xDFFF => Instruction::Drw(vx, vy, n),
+        0xE000..=0xEFFF => match nn {
+            0x9E => Instruction::Skp(vx),
+            0xA1 => Instruction::Skpn(vx),
+            _ => return None,
+        },
+        0xF000..=0xFFFF => match nn {
+            0x00 if opcode == 0xF000 => Instruction::LdIndexLong(next?),
+            0x02 if opcode == 0xF002 => Instruction::Audio,
+            0x01 => Instruction::Plane(opcode >> 8 & 0xF),
+            0x07 => Instruction::LdRegDelay(vx, 0),
+            0x0A => Instruction::LdRegKey(vx, 0),
+            0x15 => Instruction::LdDelayReg(0, vx),
+            0x18 => Instruction::LdSoundReg(0, vx),
+            0x1E => Instruction::AddIndexReg(0, vx),
+            0x29 => Instruction::LdFReg(vx),
+            0x30 => Instruction::LdHfReg(vx),
+            0x33 => Instruction::LdBReg(vx),
+            0x3A => Instruction::Pitch(vx),
+            0x55 => Instruction::LdMemIndexReg(vx),
+            0x65 => Instruction::LdRegMemIndex(vx),
+            0x75 => Instruction::LdRReg(vx),
+            0x85 => Instruction::LdRegR(vx),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(instruction)
+}
+
+// Number of bytes `instruction` takes up.
+pub fn size(instruction: &Instruction) -> u16 {
+    match instruction {
+        Instruction::LdIndexLong(_) => 4,
+        _ => 2,
+    }
+}
+
+fn register(nibble: u16) -> Register {
+    Register::try_from((nibble & 0xF) as u8).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, size};
+    use chip_assembler::assembler::encode;
+    use chip_assembler::parser::Instruction;
+    use chip_lexer::token::Register;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(0x00E0, None), Some(Instruction::Cls));
+        assert_eq!(
+            decode(0x8AB4, None),
+            Some(Instruction::AddRegReg(Register::Va, Register::Vb))
+        );
+        assert_eq!(
+            decode(0xD125, None),
+            Some(Instruction::Drw(Register::V1, Register::V2, 5))
+        );
+        assert_eq!(decode(0xF201, None), Some(Instruction::Plane(2)));
+        assert_eq!(decode(0x0123, None), None);
+        assert_eq!(decode(0x5121, None), None);
+
+        let long = decode(0xF000, Some(0x1234)).unwrap();
+        assert_eq!(long, Instruction::LdIndexLong(0x1234));
+        assert_eq!(size(&long), 4);
+        assert_eq!(decode(0xF000, None), None);
+    }
+
+    #[test]
+    fn test_decode_matches_assembler() {
+        // Assembling a decoded instruction gives back the original opcode.
+        for opcode in 0..=0xFFFF {
+            if let Some(instruction) = decode(opcode, Some(0x1234)) {
+                assert_eq!(encode(instruction).0, opcode, "{:?}", instruction);
+            }
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use crate::decoder::{decode, size};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use chip_assembler::parser::Instruction;
+use core::fmt::{self, Display, Formatter};
+
+// Most data bytes written on a single `db` line.
+const BYTES_PER_LINE: usize = 8;
+
+pub struct Disassembler<'d> {
+    rom: &'d [u8],
+    origin: u16,
+}
+
+impl<'d> From<&'d [u8]> for Disassembler<'d> {
+    fn from(rom: &'d [u8]) -> Self {
+        Self { rom, origin: 0x200 }
+    }
+}
+
+impl<'d> Disassembler<'d> {
+    // Address the ROM is loaded at and starts executing from.
+    pub fn with_origin(mut self, origin: u16) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn disassemble(&self) -> Listing {
+        let (code, targets) = self.trace();
+        let end = self.origin as usize + self.rom.len();
+
+        // Lines may only start at these, so every label has a line to go on.
+        let boundaries: BTreeSet<usize> = code
+            .keys()
+            .chain(targets.keys())
+            .map(|&address| address as usize)
+            .collect();
+        let next_boundary = |address: usize| {
+            boundaries
+                .range(address + 1..)
+                .next()
+                .copied()
+                .unwrap_or(usize::MAX)
+        };
+
+        let mut lines = Vec::new();
+        let mut address = self.origin as usize;
+
+        while address < end {
+            if let Some(&instruction) = code.get(&(address as u16)) {
+                let size = size(&instruction) as usize;
+
+                // Instructions overlapping another one or a label are written as data.
+                if address + size <= end && next_boundary(address) >= address + size {
+                    lines.push(Line::Instruction {
+                        address: address as u16,
+                        instruction,
+                    });
+
+                    address += size;
+                    continue;
+                }
+            }
+
+            let stop = end
+                .min(address + BYTES_PER_LINE)
+                .min(next_boundary(address));
+            let offset = address - self.origin as usize;
+
+            lines.push(Line::Data {
+                address: address as u16,
+                bytes: self.rom[offset..offset + stop - address].to_vec(),
+            });
+
+            address = stop;
+        }
+
+        let labels = targets
+            .into_iter()
+            .filter(|&(address, _)| lines.iter().any(|line| line.address() == address))
+            .map(|(address, subroutine)| {
+                let name = if subroutine {
+                    format!("sub_{:03x}", address)
+                } else {
+                    format!("label_{:03x}", address)
+                };
+
+                (address, name)
+            })
+            .collect();
+
+        Listing {
+            origin: self.origin,
+            lines,
+            labels,
+        }
+    }
+
+    // Follows every path from the origin, returning the instructions reached and the jump
+    // targets, which are true for subroutines.
+    fn trace(&self) -> (BTreeMap<u16, Instruction>, BTreeMap<u16, bool>) {
+        let mut code = BTreeMap::new();
+        let mut targets = BTreeMap::new();
+        let mut pending = vec![self.origin];
+
+        while let Some(address) = pending.pop() {
+            if code.contains_key(&address) {
+                continue;
+            }
+
+            let instruction = match self.decode(address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+
+            code.insert(address, instruction);
+
+            let next = address.wrapping_add(size(&instruction));
+
+            match instruction {
+                Instruction::JmpAddress(target) => {
+                    targets.entry(target).or_insert(false);
+                    pending.push(target);
+                }
+                // The target is the start of a jump table, the entries are found from there.
+                Instruction::JmpRegAddress(_, target) => {
+                    targets.entry(target).or_insert(false);
+                    pending.push(target);
+                }
+                Instruction::Call(target) => {
+                    targets.insert(target, true);
+                    pending.extend([target, next]);
+                }
+                Instruction::Ret | Instruction::Exit => (),
+                Instruction::SeRegVal(..)
+                | Instruction::SneRegVal(..)
+                | Instruction::SeRegReg(..)
+                | Instruction::SneRegReg(..)
+                | Instruction::Skp(_)
+                | Instruction::Skpn(_) => {
+                    // XO-CHIP skips over the whole of a four byte instruction.
+                    let skipped = match self.word(next) {
+                        Some(0xF000) => 4,
+                        _ => 2,
+                    };
+
+                    pending.extend([next, next.wrapping_add(skipped)]);
+                }
+                _ => pending.push(next),
+            }
+        }
+
+        (code, targets)
+    }
+
+    fn decode(&self, address: u16) -> Option<Instruction> {
+        decode(self.word(address)?, self.word(address.wrapping_add(2)))
+    }
+
+    fn word(&self, address: u16) -> Option<u16> {
+        let offset = address.checked_sub(self.origin)? as usize;
+        let bytes = self.rom.get(offset..offset + 2)?;
+
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Instruction {
+        address: u16,
+        instruction: Instruction,
+    },
+    Data {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+}
+
+impl Line {
+    pub fn address(&self) -> u16 {
+        match self {
+            Self::Instruction { address, .. } | Self::Data { address, .. } => *address,
+        }
+    }
+}
+
+// Disassembled ROM, displays as source the assembler turns back into the same bytes.
+#[derive(Debug, Clone)]
+pub struct Listing {
+    origin: u16,
+    lines: Vec<Line>,
+    labels: BTreeMap<u16, String>,
+}
+
+impl Listing {
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = (u16, Instruction)> + '_ {
+        self.lines.iter().filter_map(|line| match *line {
+            Line::Instruction {
+                address,
+                instruction,
+            } => Some((address, instruction)),
+            Line::Data { .. } => None,
+        })
+    }
+
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(String::as_str)
+    }
+
+    fn address(&self, address: u16) -> String {
+        match self.label(address) {
+            Some(label) => String::from(label),
+            None => format!("0x{:03X}", address),
+        }
+    }
+}
+
+impl Display for Listing {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.origin != 0x200 {
+            writeln!(f, "    org 0x{:03X}", self.origin)?;
+        }
+
+        for line in &self.lines {
+            if let Some(label) = self.label(line.address()) {
+                writeln!(f, "{}:", label)?;
+            }
+
+            match line {
+                Line::Instruction { instruction, .. } => {
+                    write!(f, "    ")?;
+                    self.fmt_instruction(f, instruction)?;
+                    writeln!(f)?;
+                }
+                Line::Data { bytes, .. } => {
+                    write!(f, "    db ")?;
+
+                    for (i, byte) in bytes.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+
+                        write!(f, "0x{:02X}", byte)?;
+                    }
+
+                    writeln!(f)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Listing {
+    fn fmt_instruction(&self, f: &mut Formatter<'_>, instruction: &Instruction) -> fmt::Result {
+        let v = |register| register as u8;
+
+        match *instruction {
+            Instruction::Cls => write!(f, "cls"),
+            Instruction::Ret => write!(f, "ret"),
+            Instruction::JmpAddress(nnn) => write!(f, "jmp {}", self.address(nnn)),
+            Instruction::Call(nnn) => write!(f, "call {}", self.address(nnn)),
+            Instruction::SeRegVal(x, nn) => write!(f, "se v{:x}, 0x{:02X}", v(x), nn),
+            Instruction::SneRegVal(x, nn) => write!(f, "sne v{:x}, 0x{:02X}", v(x), nn),
+            Instruction::SeRegReg(x, y) => write!(f, "se v{:x}, v{:x}", v(x), v(y)),
+            Instruction::LdRegVal(x, nn) => write!(f, "ld v{:x}, 0x{:02X}", v(x), nn),
+            Instruction::AddRegVal(x, nn) => write!(f, "add v{:x}, 0x{:02X}", v(x), nn),
+            Instruction::LdRegReg(x, y) => write!(f, "ld v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Or(x, y) => write!(f, "or v{:x}, v{:x}", v(x), v(y)),
+            Instruction::And(x, y) => write!(f, "and v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Xor(x, y) => write!(f, "xor v{:x}, v{:x}", v(x), v(y)),
+            Instruction::AddRegReg(x, y) => write!(f, "add v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Sub(x, y) => write!(f, "sub v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Shr(x, y) => write!(f, "shr v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Subn(x, y) => write!(f, "subn v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Shl(x, y) => write!(f, "shl v{:x}, v{:x}", v(x), v(y)),
+            Instruction::SneRegReg(x, y) => write!(f, "sne v{:x}, v{:x}", v(x), v(y)),
+            Instruction::LdIndex(_, nnn) => write!(f, "ld i, 0x{:03X}", nnn),
+            Instruction::JmpRegAddress(x, nnn) => {
+                write!(f, "jmp v{:x}, {}", v(x), self.address(nnn))
+            }
+            Instruction::Rnd(x, nn) => write!(f, "rnd v{:x}, 0x{:02X}", v(x), nn),
+            Instruction::Drw(x, y, n) => write!(f, "drw v{:x}, v{:x}, {}", v(x), v(y), n),
+            Instruction::Skp(x) => write!(f, "skp v{:x}", v(x)),
+            Instruction::Skpn(x) => write!(f, "sknp v{:x}", v(x)),
+            Instruction::LdRegDelay(x, _) => write!(f, "ld v{:x}, dt", v(x)),
+            Instruction::LdRegKey(x, _) => write!(f, "ld v{:x}, k", v(x)),
+            Instruction::LdDelayReg(_, x) => write!(f, "ld dt, v{:x}", v(x)),
+            Instruction::LdSoundReg(_, x) => write!(f, "ld st, v{:x}", v(x)),
+            Instruction::AddIndexReg(_, x) => write!(f, "add i, v{:x}", v(x)),
+            Instruction::LdFReg(x) => write!(f, "ld f, v{:x}", v(x)),
+            Instruction::LdBReg(x) => write!(f, "ld b, v{:x}", v(x)),
+            Instruction::LdMemIndexReg(x) => write!(f, "ld [i], v{:x}", v(x)),
+            Instruction::LdRegMemIndex(x) => write!(f, "ld v{:x}, [i]", v(x)),
+            Instruction::Scd(n) => write!(f, "scd {}", n),
+            Instruction::Scr => write!(f, "scr"),
+            Instruction::Scl => write!(f, "scl"),
+            Instruction::Exit => write!(f, "exit"),
+            Instruction::Low => write!(f, "low"),
+            Instruction::High => write!(f, "high"),
+            Instruction::LdHfReg(x) => write!(f, "ld hf, v{:x}", v(x)),
+            Instruction::LdRReg(x) => write!(f, "ld r, v{:x}", v(x)),
+            Instruction::LdRegR(x) => write!(f, "ld v{:x}, r", v(x)),
+            Instruction::LdIndexLong(nnnn) => write!(f, "ld i, long 0x{:04X}", nnnn),
+            Instruction::Save(x, y) => write!(f, "save v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Load(x, y) => write!(f, "load v{:x}, v{:x}", v(x), v(y)),
+            Instruction::Plane(n) => write!(f, "plane {}", n),
+            Instruction::Audio => write!(f, "audio"),
+            Instruction::Pitch(x) => write!(f, "pitch v{:x}", v(x)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Disassembler, Line};
+    use crate::decoder::decode;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use chip_assembler::assembler::Assembler;
+
+    #[test]
+    fn test_code_and_data() {
+        let rom = [
+            0x00, 0xE0, // cls
+            0xA2, 0x0A, // ld i, 0x20A
+            0x22, 0x08, // call 0x208
+            0x12, 0x06, // jmp 0x206
+            0x00, 0xEE, // ret
+            0x3C, 0x42, 0xFF, // sprite
+        ];
+
+        let listing = Disassembler::from(&rom[..]).disassemble();
+
+        assert_eq!(
+            listing.to_string(),
+            "    cls\n    ld i, 0x20A\n    call sub_208\nlabel_206:\n    jmp label_206\n\
+             sub_208:\n    ret\n    db 0x3C, 0x42, 0xFF\n"
+        );
+        assert_eq!(listing.instructions().count(), 5);
+        assert_eq!(
+            listing.lines().last(),
+            Some(&Line::Data {
+                address: 0x20A,
+                bytes: vec![0x3C, 0x42, 0xFF]
+            })
+        );
+    }
+
+    #[test]
+    fn test_skips() {
+        let rom = [
+            0x30, 0x00, // se v0, 0x00
+            0xF0, 0x00, 0x12, 0x34, // ld i, long 0x1234
+            0x00, 0xFD, // exit
+            0xFF, 0xFF, // unreachable
+        ];
+
+        let listing = Disassembler::from(&rom[..]).disassemble();
+
+        assert_eq!(
+            listing.to_string(),
+            "    se v0, 0x00\n    ld i, long 0x1234\n    exit\n    db 0xFF, 0xFF\n"
+        );
+    }
+
+    #[test]
+    fn test_overlapping_target() {
+        let rom = [
+            0x12, 0x04, // jmp 0x204
+            0xF0, 0x00, 0x12, 0x06, // ld i, long 0x1206
+            0x12, 0x06, // jmp 0x206, into the middle of the instruction before
+        ];
+
+        let listing = Disassembler::from(&rom[..])
+            .with_origin(0x202)
+            .disassemble();
+
+        // The overlapped instruction is written as data so the target gets its own line.
+        assert_eq!(
+            listing.to_string(),
+            "    org 0x202\n    jmp label_204\nlabel_204:\n    db 0xF0, 0x00\n\
+             label_206:\n    jmp label_206\n    jmp label_206\n"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rom = [
+            0x00, 0xE0, // cls
+            0x23, 0x09, // call 0x309
+            0x13, 0x04, // jmp 0x304
+            0x01, 0x02, 0x03, // data
+            0x00, 0xEE, // ret, at an odd address
+        ];
+
+        let source = Disassembler::from(&rom[..])
+            .with_origin(0x300)
+            .disassemble()
+            .to_string();
+
+        // The listing's `org` pads the output from the load address.
+        let bytes: Vec<u8> = Assembler::from(source.as_str()).collect();
+        assert_eq!(bytes[0x100..], rom);
+    }
+
+    #[test]
+    fn test_round_trip_all() {
+        // Every instruction the decoder knows assembles back from its listing.
+        for opcode in 0..=0xFFFFu16 {
+            let rom = [opcode.to_be_bytes(), 0xABCDu16.to_be_bytes()].concat();
+
+            if decode(opcode, Some(0xABCD)).is_none() {
+                continue;
+            }
+
+            let listing = Disassembler::from(&rom[..]).disassemble();
+            let source = listing.to_string();
+            let bytes: Vec<u8> = Assembler::from(source.as_str()).collect();
+
+            assert_eq!(bytes, rom, "{}", source);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+#![no_std]
+
+extern crate alloc;
+
+pub mod decoder;
+pub mod disassembler;
@@ -0,0 +1,170 @@
+use alloc::string::{String, ToString};
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Lower,
+    Upper,
+}
+
+impl Display for Case {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Lower => write!(f, "lower"),
+            Self::Upper => write!(f, "upper"),
+        }
+    }
+}
+
+// Radix of the numbers in instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Immediates {
+    Keep,
+    Hex,
+    Decimal,
+}
+
+impl Display for Immediates {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Keep => write!(f, "keep"),
+            Self::Hex => write!(f, "hex"),
+            Self::Decimal => write!(f, "decimal"),
+        }
+    }
+}
+
+// Style of the formatted source, columns count from the start of the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    // Where instructions and directives start.
+    pub indent: usize,
+    // Where the operands start, after the mnemonic.
+    pub operand_column: usize,
+    // Where comments after code start.
+    pub comment_column: usize,
+    // Of mnemonics, directives and registers, labels keep theirs.
+    pub case: Case,
+    pub immediates: Immediates,
+    pub max_blank_lines: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            operand_column: 12,
+            comment_column: 32,
+            case: Case::Lower,
+            immediates: Immediates::Keep,
+            max_blank_lines: 1,
+        }
+    }
+}
+
+impl Config {
+    // Changes the setting `key` to `value`, both as written in a `chipfmt.toml`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidValue(key.to_string(), value.to_string());
+
+        match key {
+            "indent" => self.indent = value.parse().map_err(|_| invalid())?,
+            "operand_column" => self.operand_column = value.parse().map_err(|_| invalid())?,
+            "comment_column" => self.comment_column = value.parse().map_err(|_| invalid())?,
+            "case" => {
+                self.case = match value {
+                    "lower" => Case::Lower,
+                    "upper" => Case::Upper,
+                    _ => return Err(invalid()),
+                }
+            }
+            "immediates" => {
+                self.immediates = match value {
+                    "keep" => Immediates::Keep,
+                    "hex" => Immediates::Hex,
+                    "decimal" => Immediates::Decimal,
+                    _ => return Err(invalid()),
+                }
+            }
+            "max_blank_lines" => self.max_blank_lines = value.parse().map_err(|_| invalid())?,
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+// The settings as a `chipfmt.toml`.
+impl Display for Config {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "indent = {}", self.indent)?;
+        writeln!(f, "operand_column = {}", self.operand_column)?;
+        writeln!(f, "comment_column = {}", self.comment_column)?;
+        writeln!(f, "case = \"{}\"", self.case)?;
+        writeln!(f, "immediates = \"{}\"", self.immediates)?;
+        writeln!(f, "max_blank_lines = {}", self.max_blank_lines)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    UnknownKey(String),
+    InvalidValue(String, String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnknownKey(key) => write!(f, "There is no setting {}", key),
+            Self::InvalidValue(key, value) => write!(f, "{} isn't a valid {}", value, key),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Case, Config, ConfigError, Immediates};
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_set() {
+        let mut config = Config::default();
+
+        config.set("indent", "2").unwrap();
+        config.set("case", "upper").unwrap();
+        config.set("immediates", "decimal").unwrap();
+
+        assert_eq!(config.indent, 2);
+        assert_eq!(config.case, Case::Upper);
+        assert_eq!(config.immediates, Immediates::Decimal);
+
+        assert_eq!(
+            config.set("indent", "-1"),
+            Err(ConfigError::InvalidValue(
+                "indent".to_string(),
+                "-1".to_string()
+            ))
+        );
+        assert_eq!(
+            config.set("tabs", "true"),
+            Err(ConfigError::UnknownKey("tabs".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let config = Config {
+            max_blank_lines: 2,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            config.to_string(),
+            "indent = 4\noperand_column = 12\ncomment_column = 32\ncase = \"lower\"\n\
+             immediates = \"keep\"\nmax_blank_lines = 2\n"
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chip_lexer::lexer::Lexer;
+use chip_lexer::span::Spanned;
+use chip_lexer::token::{Delimeter, Directive, Operator, Token};
+
+use crate::config::{Case, Config, Immediates};
+
+pub struct Formatter<'f> {
+    source: &'f str,
+    config: Config,
+}
+
+impl<'f> From<&'f str> for Formatter<'f> {
+    fn from(source: &'f str) -> Self {
+        Self {
+            source,
+            config: Config::default(),
+        }
+    }
+}
+
+impl<'f> Formatter<'f> {
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    // The formatted source, formatting it again changes nothing.
+    pub fn format(&self) -> String {
+        let mut lines = Vec::new();
+
+        for line in split(self.source) {
+            self.format_line(&line, &mut lines);
+        }
+
+        let mut output = String::new();
+        let mut previous = None;
+        let mut blanks = 0;
+
+        // Blank lines are only kept between other lines, so none start or end the file.
+        for (kind, text) in lines {
+            if kind == Kind::Blank {
+                blanks += 1;
+                continue;
+            }
+
+            let wanted = match (previous, kind) {
+                (None, _) | (Some(Kind::Label), _) => 0,
+                // A comment right above a label belongs to it.
+                (Some(Kind::Comment), Kind::Label) => blanks,
+                (Some(_), Kind::Label) => blanks.max(1),
+                _ => blanks,
+            };
+
+            for _ in 0..wanted.min(self.config.max_blank_lines) {
+                output.push('\n');
+            }
+
+            output.push_str(&text);
+            output.push('\n');
+
+            previous = Some(kind);
+            blanks = 0;
+        }
+
+        output
+    }
+
+    fn format_line(&self, line: &Line<'f>, lines: &mut Vec<(Kind, String)>) {
+        match &line.tokens[..] {
+            [] => match line.comment {
+                Some(comment) => {
+                    let indent = if line.indented { self.config.indent } else { 0 };
+                    lines.push((Kind::Comment, pad(String::new(), indent) + comment));
+                }
+                None => lines.push((Kind::Blank, String::new())),
+            },
+            [name, colon, rest @ ..]
+                if matches!(name.value(), Token::Identifier(_))
+                    && colon.value() == &Token::Delimeter(Delimeter::Colon) =>
+            {
+                let label = format!("{}:", name.text());
+
+                // Code after a label goes on its own line.
+                if rest.is_empty() {
+                    lines.push((Kind::Label, self.with_comment(label, line.comment)));
+                } else {
+                    lines.push((Kind::Label, label));
+                    lines.push((Kind::Code, self.format_code(rest, line.comment)));
+                }
+            }
+            tokens => lines.push((Kind::Code, self.format_code(tokens, line.comment))),
+        }
+    }
+
+    fn format_code(&self, tokens: &[Spanned<'f, Token<'f>>], comment: Option<&str>) -> String {
+        let code = match tokens {
+            [name, equ, rest @ ..]
+                if matches!(name.value(), Token::Identifier(_))
+                    && equ.value() == &Token::Directive(Directive::Equ) =>
+            {
+                let value = self.operands(rest, Immediates::Keep);
+
+                format!(
+                    "{} {} {}",
+                    name.text(),
+                    self.word(equ, Immediates::Keep),
+                    value
+                )
+            }
+            [first, rest @ ..] => {
+                let mnemonic = self.word(first, Immediates::Keep);
+                let mnemonic = pad(String::new(), self.config.indent) + &mnemonic;
+
+                // Numbers of directives are data, which keep how they are written.
+                let immediates = match first.value() {
+                    Token::Mnemonic(_) => self.config.immediates,
+                    _ => Immediates::Keep,
+                };
+
+                match rest.is_empty() {
+                    true => mnemonic,
+                    false => {
+                        let operands = self.operands(rest, immediates);
+                        pad(mnemonic, self.config.operand_column) + &operands
+                    }
+                }
+            }
+            [] => String::new(),
+        };
+
+        self.with_comment(code, comment)
+    }
+
+    fn with_comment(&self, code: String, comment: Option<&str>) -> String {
+        match comment {
+            Some(comment) => pad(code, self.config.comment_column) + comment,
+            None => code,
+        }
+    }
+
+    // Operands separated by commas, with spaces around binary operators only.
+    fn operands(&self, tokens: &[Spanned<'f, Token<'f>>], immediates: Immediates) -> String {
+        let mut operands = String::new();
+        let mut previous = Previous::Start;
+
+        for token in tokens {
+            let space = match (previous, token.value()) {
+                (Previous::Start | Previous::Open | Previous::Unary, _) => false,
+                (_, Token::Delimeter(Delimeter::Comma | Delimeter::RightParen)) => false,
+                (_, Token::Delimeter(Delimeter::RightBracket)) => false,
+                // Calls like `hi(address)`.
+                (Previous::Operand, Token::Delimeter(Delimeter::LeftParen)) => false,
+                _ => true,
+            };
+
+            if space {
+                operands.push(' ');
+            }
+
+            operands.push_str(&self.word(token, immediates));
+
+            previous = match token.value() {
+                Token::Delimeter(Delimeter::Comma) => Previous::Comma,
+                Token::Delimeter(Delimeter::LeftParen | Delimeter::LeftBracket) => Previous::Open,
+                Token::Operator(Operator::Tilde) => Previous::Unary,
+                Token::Operator(_) if previous != Previous::Operand => Previous::Unary,
+                Token::Operator(_) => Previous::Binary,
+                _ => Previous::Operand,
+            };
+        }
+
+        operands
+    }
+
+    fn word(&self, token: &Spanned<'f, Token<'f>>, immediates: Immediates) -> String {
+        let text = token.text();
+
+        match token.value() {
+            Token::Mnemonic(_) | Token::Directive(_) | Token::Register(_) | Token::Keyword(_) => {
+                match self.config.case {
+                    Case::Lower => text.to_ascii_lowercase(),
+                    Case::Upper => text.to_ascii_uppercase(),
+                }
+            }
+            // Hex is as wide as a byte, an address or a word.
+            &Token::Number(number) => match (immediates, text.starts_with("0x")) {
+                (Immediates::Hex, _) | (Immediates::Keep, true) => {
+                    let width = match number {
+                        0..=0xFF => 2,
+                        0x100..=0xFFF => 3,
+                        _ => 4,
+                    };
+
+                    format!("0x{:01$X}", number, width)
+                }
+                (Immediates::Decimal, _) => number.to_string(),
+                (Immediates::Keep, false) => text.to_string(),
+            },
+            _ => text.to_string(),
+        }
+    }
+}
+
+// What came before a token in the operands, to space it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Previous {
+    Start,
+    Open,
+    Comma,
+    Unary,
+    Binary,
+    Operand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Blank,
+    Comment,
+    Label,
+    Code,
+}
+
+// A line of the source without its whitespace.
+#[derive(Default)]
+struct Line<'l> {
+    tokens: Vec<Spanned<'l, Token<'l>>>,
+    comment: Option<&'l str>,
+    // Whether it starts with whitespace.
+    indented: bool,
+}
+
+fn split(source: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut line = Line::default();
+
+    for token in Lexer::from(source).with_trivia() {
+        match *token.value() {
+            Token::Newline => lines.push(core::mem::take(&mut line)),
+            Token::Whitespace(_) => {
+                line.indented |= line.tokens.is_empty() && line.comment.is_none();
+            }
+            Token::Comment(comment) => line.comment = Some(comment.trim_end()),
+            _ => line.tokens.push(token),
+        }
+    }
+
+    lines.push(line);
+    lines
+}
+
+// Pads `text` up to `column`, text reaching it is still separated by a space.
+fn pad(mut text: String, column: usize) -> String {
+    let len = text.chars().count();
+
+    if len > 0 && len >= column {
+        text.push(' ');
+    }
+
+    for _ in len..column {
+        text.push(' ');
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Formatter;
+    use crate::config::{Case, Config, Immediates};
+    use alloc::vec::Vec;
+    use chip_assembler::assembler::Assembler;
+
+    const SOURCES: [&str; 3] = [
+        "; Draws a sprite\nSPEED   equ 0x4\n\n\n  start: CLS\nLD v0,0x1 ; x\n   ld I,sprite\n\
+         draw:\n\n drw V0 , v1 , 3\n\tadd v0,-SPEED+ ~0x0F&0xF\n jmp draw ; again\n\
+         sprite: db 0x18,0x3C , hi( sprite ),lo(sprite)\n\n\n",
+        "\r\nloop:    ld [ i ],v3 ; save\r\n\r\n\r\n    ld v3,[i]\r\n  ; indented\r\n   jmp loop",
+        "",
+    ];
+
+    #[test]
+    fn test_format() {
+        let formatted = Formatter::from(SOURCES[0]).format();
+
+        assert_eq!(
+            formatted,
+            "; Draws a sprite\n\
+             SPEED equ 0x04\n\
+             \n\
+             start:\n    cls\n    ld      v0, 0x01            ; x\n    ld      i, sprite\n\
+             \n\
+             draw:\n    drw     v0, v1, 3\n    add     v0, -SPEED + ~0x0F & 0x0F\n\
+             \x20   jmp     draw                ; again\n\
+             \n\
+             sprite:\n    db      0x18, 0x3C, hi(sprite), lo(sprite)\n"
+        );
+    }
+
+    #[test]
+    fn test_upper_case() {
+        let config = Config {
+            case: Case::Upper,
+            operand_column: 0,
+            max_blank_lines: 0,
+            ..Config::default()
+        };
+
+        let formatted = Formatter::from(SOURCES[1]).with_config(config).format();
+
+        assert_eq!(
+            formatted,
+            "loop:\n    LD [I], V3                  ; save\n    LD V3, [I]\n    ; indented\n\
+             \x20   JMP loop\n"
+        );
+    }
+
+    #[test]
+    fn test_immediates() {
+        let source = "ld v0, 0x0A\ndrw v0, v1, 15\ndb 10, 0x1";
+
+        let config = Config {
+            immediates: Immediates::Decimal,
+            ..Config::default()
+        };
+        let formatted = Formatter::from(source).with_config(config).format();
+        assert_eq!(
+            formatted,
+            "    ld      v0, 10\n    drw     v0, v1, 15\n    db      10, 0x01\n"
+        );
+
+        let config = Config {
+            immediates: Immediates::Hex,
+            ..Config::default()
+        };
+        let formatted = Formatter::from(source).with_config(config).format();
+        assert_eq!(
+            formatted,
+            "    ld      v0, 0x0A\n    drw     v0, v1, 0x0F\n    db      10, 0x01\n"
+        );
+    }
+
+    #[test]
+    fn test_idempotent() {
+        let configs = [
+            Config::default(),
+            Config {
+                case: Case::Upper,
+                indent: 2,
+                operand_column: 0,
+                comment_column: 0,
+                immediates: Immediates::Hex,
+                max_blank_lines: 3,
+            },
+        ];
+
+        for config in configs {
+            for source in SOURCES {
+                let once = Formatter::from(source).with_config(config.clone()).format();
+                let twice = Formatter::from(once.as_str())
+                    .with_config(config.clone())
+                    .format();
+
+                assert_eq!(once, twice);
+
+                // Formatting never changes what the source assembles to.
+                let before: Vec<u8> = Assembler::from(source).collect();
+                let after: Vec<u8> = Assembler::from(once.as_str()).collect();
+
+                assert_eq!(before, after);
+            }
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use crate::interpreter::{Access, ExecError, Interpreter};
+use crate::random::{RandomSource, Xorshift};
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+use core::ops::Deref;
+use core::str::FromStr;
+
+// Controls execution of the wrapped interpreter for debugging tools.
+//
+// Instructions are executed one at a time, the timers still tick after every
+// `instructions_per_frame` instructions so programs see the same timing as in `run_frame`.
+pub struct Debugger<R = Xorshift> {
+    interpreter: Interpreter<R>,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<(WatchpointId, Watchpoint)>,
+    next_watchpoint: usize,
+    executed: u32,
+    step_limit: usize,
+    // Where the last breakpoint, step or return stopped, which doesn't stop the next run again.
+    stopped: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watchpoint {
+    // Stops after an instruction accessed any of the `len` bytes at `address`, `access` of `None`
+    // matches both reads and writes.
+    Memory {
+        address: u16,
+        len: u16,
+        access: Option<Access>,
+    },
+    // Stops after the register changed.
+    Register(Register),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Step,
+    Breakpoint(u16),
+    Watchpoint(WatchpointId),
+    // Step over or step out returned to the caller.
+    Return,
+    FrameEnd,
+    Halted,
+    // `step_limit` instructions ran without any other reason to stop.
+    Limit,
+}
+
+// Breakpoint condition comparing two operands, e.g. `v3 == 0x10` or `i >= 0x300`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub left: Operand,
+    pub comparison: Comparison,
+    pub right: Operand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Value(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+enum Until {
+    Step,
+    Frame,
+    Return { sp: u8, pc: u16 },
+    Out { sp: u8 },
+    Stop,
+}
+
+impl<R: RandomSource> Debugger<R> {
+    pub fn new(interpreter: Interpreter<R>) -> Self {
+        Self {
+            interpreter,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            next_watchpoint: 0,
+            executed: 0,
+            step_limit: 1_000_000,
+            stopped: None,
+        }
+    }
+
+    // Maximum number of instructions `step_over`, `step_out` and `resume` run before giving up.
+    pub fn with_step_limit(mut self, step_limit: usize) -> Self {
+        self.step_limit = step_limit.max(1);
+        self
+    }
+
+    pub fn into_inner(self) -> Interpreter<R> {
+        self.interpreter
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter<R> {
+        &mut self.interpreter
+    }
+
+    // Replaces the breakpoint at `address`, if any.
+    pub fn set_breakpoint(&mut self, address: u16, condition: Option<Condition>) {
+        self.remove_breakpoint(address);
+        self.breakpoints.push(Breakpoint { address, condition });
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints
+            .retain(|breakpoint| breakpoint.address != address);
+
+        self.breakpoints.len() != len
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> WatchpointId {
+        let id = WatchpointId(self.next_watchpoint);
+
+        self.next_watchpoint += 1;
+        self.watchpoints.push((id, watchpoint));
+
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|&(other, _)| other != id);
+
+        self.watchpoints.len() != len
+    }
+
+    pub fn register(&self, register: Register) -> u16 {
+        let interpreter = &self.interpreter;
+
+        match register {
+            Register::V(x) => interpreter.v[(x & 0xF) as usize] as u16,
+            Register::I => interpreter.index,
+            Register::Pc => interpreter.pc,
+            Register::Sp => interpreter.sp as u16,
+            Register::Dt => interpreter.delay_timer as u16,
+            Register::St => interpreter.sound_timer as u16,
+        }
+    }
+
+    // Values are truncated to the width of the register.
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        let interpreter = &mut self.interpreter;
+
+        match register {
+            Register::V(x) => interpreter.v[(x & 0xF) as usize] = value as u8,
+            Register::I => interpreter.index = value,
+            Register::Pc => interpreter.pc = value,
+            Register::Sp => interpreter.sp = (value as usize).min(interpreter.stack.len()) as u8,
+            Register::Dt => interpreter.delay_timer = value as u8,
+            Register::St => interpreter.sound_timer = value as u8,
+        }
+    }
+
+    // Return addresses, innermost call last.
+    pub fn stack(&self) -> &[u16] {
+        &self.interpreter.stack[..self.interpreter.sp as usize]
+    }
+
+    pub fn stack_mut(&mut self) -> &mut [u16] {
+        &mut self.interpreter.stack[..self.interpreter.sp as usize]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.interpreter.memory[..self.interpreter.memory_size]
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.interpreter.memory[..self.interpreter.memory_size]
+    }
+
+    // Executes a single instruction.
+    pub fn step(&mut self) -> Result<StopReason, ExecError> {
+        self.run(Until::Step)
+    }
+
+    // Like `step`, but runs a CALL until it returned.
+    pub fn step_over(&mut self) -> Result<StopReason, ExecError> {
+        let opcode = self
+            .memory()
+            .get(self.interpreter.pc as usize..self.interpreter.pc as usize + 2)
+            .map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+
+        if opcode & 0xF000 != 0x2000 {
+            return self.step();
+        }
+
+        // A call at the top of memory can't return, running it ends in `PcOutOfRange`.
+        let len = self
+            .interpreter
+            .instruction_len(self.interpreter.pc as usize);
+
+        self.run(Until::Return {
+            sp: self.interpreter.sp,
+            pc: self.interpreter.pc.wrapping_add(len),
+        })
+    }
+
+    // Runs until the current subroutine returned.
+    pub fn step_out(&mut self) -> Result<StopReason, ExecError> {
+        self.run(Until::Out {
+            sp: self.interpreter.sp,
+        })
+    }
+
+    // Runs the rest of the current frame.
+    pub fn run_frame(&mut self) -> Result<StopReason, ExecError> {
+        self.run(Until::Frame)
+    }
+
+    // Runs until a breakpoint or watchpoint is hit.
+    pub fn resume(&mut self) -> Result<StopReason, ExecError> {
+        self.run(Until::Stop)
+    }
+
+    fn run(&mut self, until: Until) -> Result<StopReason, ExecError> {
+        let stopped = self.stopped.take();
+
+        for executed in 0..self.step_limit {
+            if self.interpreter.halted {
+                return Ok(StopReason::Halted);
+            }
+
+            let pc = self.interpreter.pc;
+
+            if (executed > 0 || stopped != Some(pc)) && self.is_at_breakpoint() {
+                self.stopped = Some(pc);
+                return Ok(StopReason::Breakpoint(pc));
+            }
+
+            if let Some(id) = self.execute()? {
+                return Ok(StopReason::Watchpoint(id));
+            }
+
+            let interpreter = &self.interpreter;
+
+            let reason = match until {
+                Until::Step => Some(StopReason::Step),
+                Until::Frame if self.executed == 0 => Some(StopReason::FrameEnd),
+                Until::Return { sp, pc } if interpreter.sp == sp && interpreter.pc == pc => {
+                    Some(StopReason::Return)
+                }
+                Until::Out { sp } if interpreter.sp < sp => Some(StopReason::Return),
+                _ => None,
+            };
+
+            if let Some(reason) = reason {
+                if reason != StopReason::FrameEnd {
+                    self.stopped = Some(interpreter.pc);
+                }
+
+                return Ok(reason);
+            }
+        }
+
+        Ok(StopReason::Limit)
+    }
+
+    fn is_at_breakpoint(&self) -> bool {
+        self.breakpoints.iter().any(|breakpoint| {
+            breakpoint.address == self.interpreter.pc
+                && breakpoint
+                    .condition
+                    .map_or(true, |condition| self.evaluate(condition))
+        })
+    }
+
+    fn evaluate(&self, condition: Condition) -> bool {
+        let value = |operand| match operand {
+            Operand::Register(register) => self.register(register),
+            Operand::Value(value) => value,
+        };
+
+        let (left, right) = (value(condition.left), value(condition.right));
+
+        match condition.comparison {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+        }
+    }
+
+    // Executes one instruction, ticks the timers at the end of a frame and reports the first
+    // triggered watchpoint.
+    fn execute(&mut self) -> Result<Option<WatchpointId>, ExecError> {
+        let before: Vec<u16> = self
+            .watchpoints
+            .iter()
+            .map(|&(_, watchpoint)| match watchpoint {
+                Watchpoint::Register(register) => self.register(register),
+                Watchpoint::Memory { .. } => 0,
+            })
+            .collect();
+
+        self.interpreter.execute()?;
+        self.executed += 1;
+
+        if self.executed >= self.interpreter.instructions_per_frame || self.interpreter.halted {
+            self.interpreter.tick_timers();
+            self.executed = 0;
+        }
+
+        let access = self.interpreter.last_access.clone();
+
+        let triggered =
+            self.watchpoints.iter().zip(before).find(
+                |&(&(_, watchpoint), before)| match watchpoint {
+                    Watchpoint::Register(register) => self.register(register) != before,
+                    Watchpoint::Memory {
+                        address,
+                        len,
+                        access: kind,
+                    } => access.as_ref().map_or(false, |(accessed, range)| {
+                        let watched = address as usize..address as usize + len as usize;
+
+                        kind.map_or(true, |kind| kind == *accessed)
+                            && range.start < watched.end
+                            && watched.start < range.end
+                    }),
+                },
+            );
+
+        Ok(triggered.map(|(&(id, _), _)| id))
+    }
+}
+
+impl<R> Deref for Debugger<R> {
+    type Target = Interpreter<R>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.interpreter
+    }
+}
+
+impl<R: RandomSource> From<Interpreter<R>> for Debugger<R> {
+    fn from(interpreter: Interpreter<R>) -> Self {
+        Self::new(interpreter)
+    }
+}
+
+impl FromStr for Register {
+    type Err = ConditionError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let [b'v' | b'V', x] = name.as_bytes() {
+            if let Some(x) = (*x as char).to_digit(16) {
+                return Ok(Register::V(x as u8));
+            }
+        }
+
+        [
+            ("i", Register::I),
+            ("pc", Register::Pc),
+            ("sp", Register::Sp),
+            ("dt", Register::Dt),
+            ("st", Register::St),
+        ]
+        .into_iter()
+        .find(|(other, _)| name.eq_ignore_ascii_case(other))
+        .map(|(_, register)| register)
+        .ok_or(ConditionError::InvalidOperand)
+    }
+}
+
+impl FromStr for Operand {
+    type Err = ConditionError;
+
+    fn from_str(operand: &str) -> Result<Self, Self::Err> {
+        let operand = operand.trim();
+
+        let value = if let Some(hex) = operand.strip_prefix("0x") {
+            u16::from_str_radix(hex, 16)
+        } else if let Some(binary) = operand.strip_prefix("0b") {
+            u16::from_str_radix(binary, 2)
+        } else if operand.starts_with(|c: char| c.is_ascii_digit()) {
+            operand.parse()
+        } else {
+            return operand.parse().map(Operand::Register);
+        };
+
+        value
+            .map(Operand::Value)
+            .map_err(|_| ConditionError::InvalidOperand)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(condition: &str) -> Result<Self, Self::Err> {
+        let start = condition
+            .find(['=', '!', '<', '>'])
+            .ok_or(ConditionError::MissingComparison)?;
+        let (left, rest) = condition.split_at(start);
+
+        let (comparison, len) = match rest.as_bytes() {
+            [b'=', b'=', ..] => (Comparison::Eq, 2),
+            [b'!', b'=', ..] => (Comparison::Ne, 2),
+            [b'<', b'=', ..] => (Comparison::Le, 2),
+            [b'>', b'=', ..] => (Comparison::Ge, 2),
+            [b'<', ..] => (Comparison::Lt, 1),
+            [b'>', ..] => (Comparison::Gt, 1),
+            _ => return Err(ConditionError::MissingComparison),
+        };
+
+        Ok(Self {
+            left: left.parse()?,
+            comparison,
+            right: rest[len..].parse()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionError {
+    MissingComparison,
+    InvalidOperand,
+}
+
+impl Display for ConditionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::MissingComparison => {
+                write!(f, "Expected one of ==, !=, <, <=, > or >= in condition")
+            }
+            Self::InvalidOperand => write!(f, "Expected a register or number in condition"),
+        }
+    }
+}
+
+impl Error for ConditionError {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{
+        Comparison, Condition, ConditionError, Debugger, Operand, Register, StopReason, Watchpoint,
+    };
+    use crate::interpreter::{Access, ExecError, Interpreter, MAX_MEMORY};
+
+    fn debugger(program: &[u8]) -> Debugger {
+        let mut interpreter = Interpreter::default();
+        interpreter.load(program).unwrap();
+
+        Debugger::new(interpreter)
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        assert_eq!(
+            "v3 == 0x10".parse(),
+            Ok(Condition {
+                left: Operand::Register(Register::V(3)),
+                comparison: Comparison::Eq,
+                right: Operand::Value(0x10),
+            })
+        );
+        assert_eq!(
+            "I>=768".parse(),
+            Ok(Condition {
+                left: Operand::Register(Register::I),
+                comparison: Comparison::Ge,
+                right: Operand::Value(768),
+            })
+        );
+        assert_eq!(
+            "vA < vB".parse::<Condition>().map(|c| c.comparison),
+            Ok(Comparison::Lt)
+        );
+        assert_eq!(
+            "v3 0x10".parse::<Condition>(),
+            Err(ConditionError::MissingComparison)
+        );
+        assert_eq!(
+            "vg == 1".parse::<Condition>(),
+            Err(ConditionError::InvalidOperand)
+        );
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        // Counts V0 up forever.
+        let mut debugger = debugger(&[0x70, 0x01, 0x12, 0x00]);
+
+        debugger.set_breakpoint(0x202, None);
+        assert_eq!(debugger.resume(), Ok(StopReason::Breakpoint(0x202)));
+        assert_eq!(debugger.register(Register::V(0)), 1);
+
+        // Resuming from the breakpoint doesn't stop immediately.
+        assert_eq!(debugger.resume(), Ok(StopReason::Breakpoint(0x202)));
+        assert_eq!(debugger.register(Register::V(0)), 2);
+
+        assert!(debugger.remove_breakpoint(0x202));
+        assert!(!debugger.remove_breakpoint(0x202));
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut debugger = debugger(&[0x70, 0x01, 0x12, 0x00]);
+
+        debugger.set_breakpoint(0x200, Some("v0 == 0x10".parse().unwrap()));
+        assert_eq!(debugger.resume(), Ok(StopReason::Breakpoint(0x200)));
+        assert_eq!(debugger.register(Register::V(0)), 0x10);
+    }
+
+    #[test]
+    fn test_memory_watchpoint() {
+        // LD I, 0x300; LD V0, 123; LD B, V0; LD V2, [I]
+        let mut debugger = debugger(&[0xA3, 0x00, 0x60, 0x7B, 0xF0, 0x33, 0xF2, 0x65]);
+
+        let write = debugger.add_watchpoint(Watchpoint::Memory {
+            address: 0x302,
+            len: 1,
+            access: Some(Access::Write),
+        });
+        let read = debugger.add_watchpoint(Watchpoint::Memory {
+            address: 0x300,
+            len: 1,
+            access: Some(Access::Read),
+        });
+
+        assert_eq!(debugger.resume(), Ok(StopReason::Watchpoint(write)));
+        assert_eq!(debugger.pc, 0x206);
+        assert_eq!(&debugger.memory()[0x300..0x303], &[1, 2, 3]);
+
+        assert_eq!(debugger.resume(), Ok(StopReason::Watchpoint(read)));
+        assert!(debugger.remove_watchpoint(read));
+    }
+
+    #[test]
+    fn test_register_watchpoint() {
+        // V1 only changes on the third instruction.
+        let mut debugger = debugger(&[0x60, 0x01, 0x60, 0x02, 0x61, 0x05, 0x12, 0x06]);
+
+        let id = debugger.add_watchpoint(Watchpoint::Register(Register::V(1)));
+        assert_eq!(debugger.resume(), Ok(StopReason::Watchpoint(id)));
+        assert_eq!(debugger.pc, 0x206);
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        // CALL 0x206; LD V1, 1; JP 0x204; LD V0, 1; LD V0, 2; RET
+        let mut debugger = debugger(&[
+            0x22, 0x06, 0x61, 0x01, 0x12, 0x04, 0x60, 0x01, 0x60, 0x02, 0x00, 0xEE,
+        ]);
+
+        assert_eq!(debugger.step_over(), Ok(StopReason::Return));
+        assert_eq!(debugger.pc, 0x202);
+        assert_eq!(debugger.register(Register::V(0)), 2);
+
+        debugger.set_register(Register::Pc, 0x200);
+        assert_eq!(debugger.step(), Ok(StopReason::Step));
+        assert_eq!(debugger.stack(), &[0x200]);
+        assert_eq!(debugger.step(), Ok(StopReason::Step));
+
+        assert_eq!(debugger.step_out(), Ok(StopReason::Return));
+        assert_eq!(debugger.pc, 0x202);
+        assert!(debugger.stack().is_empty());
+    }
+
+    #[test]
+    fn test_step_over_at_end_of_memory() {
+        // CALL 0x300 in the last two bytes, the subroutine only returns.
+        let interpreter = Interpreter::default().with_memory_size(MAX_MEMORY);
+        let mut debugger = Debugger::new(interpreter);
+
+        debugger.memory_mut()[0x300..0x302].copy_from_slice(&[0x00, 0xEE]);
+        debugger.memory_mut()[0xFFFE..].copy_from_slice(&[0x23, 0x00]);
+        debugger.set_register(Register::Pc, 0xFFFE);
+
+        assert_eq!(debugger.step_over(), Err(ExecError::PcOutOfRange(0xFFFE)));
+    }
+
+    #[test]
+    fn test_step_limit_and_frames() {
+        // LD DT, V0 with V0 = 5, then loop.
+        let mut debugger = debugger(&[0x60, 0x05, 0xF0, 0x15, 0x12, 0x04]).with_step_limit(100);
+
+        assert_eq!(debugger.run_frame(), Ok(StopReason::FrameEnd));
+        assert_eq!(debugger.register(Register::Dt), 4);
+
+        assert_eq!(debugger.resume(), Ok(StopReason::Limit));
+        assert_eq!(debugger.register(Register::Dt), 0);
+    }
+
+    #[test]
+    fn test_breakpoint_at_frame_end() {
+        // LD V0, 1 followed by ADD V0, 1, the second frame starts at 0x20A.
+        let mut program = [0x70, 0x01].repeat(10);
+        program[0] = 0x60;
+
+        let mut debugger = debugger(&program);
+        debugger.interpreter_mut().set_instructions_per_frame(5);
+        debugger.set_breakpoint(0x20A, None);
+
+        assert_eq!(debugger.run_frame(), Ok(StopReason::FrameEnd));
+        assert_eq!(debugger.pc, 0x20A);
+
+        // The next frame stops before running the instruction at the breakpoint.
+        assert_eq!(debugger.run_frame(), Ok(StopReason::Breakpoint(0x20A)));
+        assert_eq!(debugger.register(Register::V(0)), 5);
+
+        assert_eq!(debugger.run_frame(), Ok(StopReason::FrameEnd));
+        assert_eq!(debugger.register(Register::V(0)), 10);
+    }
+}
@@ -0,0 +1,348 @@
+pub const MAX_WIDTH: usize = 128;
+pub const MAX_HEIGHT: usize = 64;
+pub const PLANES: usize = 2;
+
+// Display that switches between the 64x32 low and the 128x64 high resolution.
+//
+// Every pixel holds one bit per plane, so with both XO-CHIP planes in use a pixel is one
+// of four colours. Drawing, clearing and scrolling only touch the selected planes.
+#[derive(Clone)]
+pub struct Framebuffer {
+    pub(crate) hires: bool,
+    pub(crate) planes: u8,
+    pub(crate) pixels: [u8; MAX_WIDTH * MAX_HEIGHT],
+    pub(crate) dirty: Option<Region>,
+}
+
+// Rectangle of pixels in the coordinates of the current resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn width(&self) -> usize {
+        if self.hires {
+            MAX_WIDTH
+        } else {
+            MAX_WIDTH / 2
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            MAX_HEIGHT
+        } else {
+            MAX_HEIGHT / 2
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    // Bit mask of the planes affected by drawing, clearing and scrolling.
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    // Rows of `width()` pixels, top to bottom.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels[..self.width() * self.height()]
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels()[y * self.width() + x]
+    }
+
+    // Area changed since the host was last notified, `None` if nothing changed.
+    pub fn dirty(&self) -> Option<Region> {
+        self.dirty
+    }
+
+    pub fn clear(&mut self) {
+        let mask = !self.planes;
+
+        self.pixels.iter_mut().for_each(|pixel| *pixel &= mask);
+        self.mark_all_dirty();
+    }
+
+    pub(crate) fn take_dirty(&mut self) -> Option<Region> {
+        self.dirty.take()
+    }
+
+    pub(crate) fn mark_all_dirty(&mut self) {
+        self.dirty = Some(Region {
+            x: 0,
+            y: 0,
+            width: self.width(),
+            height: self.height(),
+        });
+    }
+
+    fn mark_dirty(&mut self, x: usize, y: usize) {
+        let pixel = Region {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        };
+
+        self.dirty = Some(self.dirty.map_or(pixel, |dirty| dirty.union(pixel)));
+    }
+
+    pub(crate) fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.pixels.fill(0);
+        self.mark_all_dirty();
+    }
+
+    pub(crate) fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << PLANES) - 1);
+    }
+
+    pub(crate) fn scroll_down(&mut self, rows: usize) {
+        self.scroll(0, rows as isize);
+    }
+
+    pub(crate) fn scroll_right(&mut self, cols: usize) {
+        self.scroll(cols as isize, 0);
+    }
+
+    pub(crate) fn scroll_left(&mut self, cols: usize) {
+        self.scroll(-(cols as isize), 0);
+    }
+
+    // Moves the selected planes by (dx, dy), pixels shifted in from the edges are blank.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.width() as isize;
+        let height = self.height() as isize;
+        let planes = self.planes;
+        let source = self.pixels;
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+
+                let moved = if (0..width).contains(&sx) && (0..height).contains(&sy) {
+                    source[(sy * width + sx) as usize] & planes
+                } else {
+                    0
+                };
+
+                let pixel = &mut self.pixels[(y * width + x) as usize];
+                *pixel = (*pixel & !planes) | moved;
+            }
+        }
+
+        self.mark_all_dirty();
+    }
+
+    // XORs a sprite of 8 pixel (or 16 pixel when `wide`) rows onto the screen and
+    // reports whether any pixel got turned off. The sprite holds one image for every
+    // selected plane, lowest plane first.
+    pub(crate) fn draw(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        wide: bool,
+        clip: bool,
+    ) -> bool {
+        let selected = self.planes;
+        let planes = (0..PLANES as u8)
+            .map(|plane| 1 << plane)
+            .filter(|&plane| selected & plane != 0);
+
+        let size = sprite.len() / selected.count_ones().max(1) as usize;
+
+        let mut collision = false;
+
+        for (plane, image) in planes.zip(sprite.chunks(size.max(1))) {
+            collision |= self.draw_plane(x, y, image, wide, clip, plane);
+        }
+
+        collision
+    }
+
+    fn draw_plane(
+        &mut self,
+        x: usize,
+        y: usize,
+        image: &[u8],
+        wide: bool,
+        clip: bool,
+        plane: u8,
+    ) -> bool {
+        let width = self.width();
+        let height = self.height();
+        let (x, y) = (x % width, y % height);
+        let bytes_per_row = if wide { 2 } else { 1 };
+
+        let mut collision = false;
+
+        for (row, bytes) in image.chunks(bytes_per_row).enumerate() {
+            if clip && y + row >= height {
+                break;
+            }
+
+            let bits = bytes
+                .iter()
+                .fold(0u16, |bits, &byte| bits << 8 | byte as u16)
+                << (16 - 8 * bytes.len());
+
+            for col in 0..8 * bytes_per_row {
+                if clip && x + col >= width {
+                    break;
+                }
+
+                if bits & (0x8000 >> col) != 0 {
+                    let (px, py) = ((x + col) % width, (y + row) % height);
+                    let pixel = &mut self.pixels[py * width + px];
+
+                    collision |= *pixel & plane != 0;
+                    *pixel ^= plane;
+
+                    self.mark_dirty(px, py);
+                }
+            }
+        }
+
+        collision
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self {
+            hires: false,
+            planes: 1,
+            pixels: [0; MAX_WIDTH * MAX_HEIGHT],
+            dirty: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Framebuffer, Region};
+
+    #[test]
+    fn test_resolution() {
+        let mut framebuffer = Framebuffer::default();
+        assert_eq!((framebuffer.width(), framebuffer.height()), (64, 32));
+        assert_eq!(framebuffer.pixels().len(), 64 * 32);
+
+        framebuffer.set_hires(true);
+        assert_eq!((framebuffer.width(), framebuffer.height()), (128, 64));
+        assert_eq!(framebuffer.pixels().len(), 128 * 64);
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut framebuffer = Framebuffer::default();
+
+        framebuffer.draw(8, 0, &[0x80], false, true);
+
+        framebuffer.scroll_down(2);
+        assert_eq!(framebuffer.pixel(8, 0), 0);
+        assert_eq!(framebuffer.pixel(8, 2), 1);
+
+        framebuffer.scroll_right(4);
+        assert_eq!(framebuffer.pixel(12, 2), 1);
+
+        framebuffer.scroll_left(4);
+        framebuffer.scroll_left(4);
+        assert_eq!(framebuffer.pixel(4, 2), 1);
+        assert_eq!(
+            framebuffer
+                .pixels()
+                .iter()
+                .filter(|&&pixel| pixel != 0)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_draw_wide_sprite() {
+        let mut framebuffer = Framebuffer::default();
+
+        assert!(!framebuffer.draw(0, 0, &[0x80, 0x01], true, true));
+        assert_eq!(framebuffer.pixel(0, 0), 1);
+        assert_eq!(framebuffer.pixel(15, 0), 1);
+
+        assert!(framebuffer.draw(0, 0, &[0x80, 0x00], true, true));
+        assert_eq!(framebuffer.pixel(0, 0), 0);
+    }
+
+    #[test]
+    fn test_planes() {
+        let mut framebuffer = Framebuffer::default();
+
+        // Both planes take their own image, one after another.
+        framebuffer.select_planes(0b11);
+        framebuffer.draw(0, 0, &[0x80, 0xC0], false, true);
+        assert_eq!(framebuffer.pixel(0, 0), 0b11);
+        assert_eq!(framebuffer.pixel(1, 0), 0b10);
+
+        // Clearing and scrolling leave the unselected plane alone.
+        framebuffer.select_planes(0b10);
+        framebuffer.scroll_down(1);
+        assert_eq!(framebuffer.pixel(0, 0), 0b01);
+        assert_eq!(framebuffer.pixel(0, 1), 0b10);
+
+        framebuffer.clear();
+        assert_eq!(framebuffer.pixel(0, 1), 0);
+        assert_eq!(framebuffer.pixel(0, 0), 0b01);
+    }
+
+    #[test]
+    fn test_dirty_region() {
+        let mut framebuffer = Framebuffer::default();
+        assert_eq!(framebuffer.take_dirty(), None);
+
+        framebuffer.draw(10, 4, &[0xC0, 0x40], false, true);
+        framebuffer.draw(20, 8, &[0x80], false, true);
+        assert_eq!(
+            framebuffer.take_dirty(),
+            Some(Region {
+                x: 10,
+                y: 4,
+                width: 11,
+                height: 5
+            })
+        );
+        assert_eq!(framebuffer.take_dirty(), None);
+
+        framebuffer.set_hires(true);
+        assert_eq!(
+            framebuffer.take_dirty(),
+            Some(Region {
+                x: 0,
+                y: 0,
+                width: 128,
+                height: 64
+            })
+        );
+    }
+}
@@ -0,0 +1,627 @@
+use crate::debugger::{Debugger, Register, StopReason, Watchpoint, WatchpointId};
+use crate::interpreter::Access;
+use crate::random::{RandomSource, Xorshift};
+use std::format;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::string::String;
+use std::vec::Vec;
+
+// Order of the registers in `g`/`G` packets and their numbers in `p`/`P` packets, values are
+// little endian.
+const REGISTERS: [(Register, &str, usize); 21] = [
+    (Register::V(0x0), "v0", 1),
+    (Register::V(0x1), "v1", 1),
+    (Register::V(0x2), "v2", 1),
+    (Register::V(0x3), "v3", 1),
+    (Register::V(0x4), "v4", 1),
+    (Register::V(0x5), "v5", 1),
+    (Register::V(0x6), "v6", 1),
+    (Register::V(0x7), "v7", 1),
+    (Register::V(0x8), "v8", 1),
+    (Register::V(0x9), "v9", 1),
+    (Register::V(0xA), "va", 1),
+    (Register::V(0xB), "vb", 1),
+    (Register::V(0xC), "vc", 1),
+    (Register::V(0xD), "vd", 1),
+    (Register::V(0xE), "ve", 1),
+    (Register::V(0xF), "vf", 1),
+    (Register::I, "i", 2),
+    (Register::Pc, "pc", 2),
+    (Register::Sp, "sp", 1),
+    (Register::Dt, "dt", 1),
+    (Register::St, "st", 1),
+];
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// GDB remote serial protocol server debugging one program over TCP, e.g. with
+// `target remote localhost:1234`.
+pub struct GdbServer<R = Xorshift> {
+    debugger: Debugger<R>,
+    watchpoints: Vec<(u8, u16, u16, WatchpointId)>,
+    no_ack: bool,
+}
+
+impl<R: RandomSource> GdbServer<R> {
+    pub fn new(debugger: Debugger<R>) -> Self {
+        Self {
+            debugger,
+            watchpoints: Vec::new(),
+            no_ack: false,
+        }
+    }
+
+    pub fn debugger(&self) -> &Debugger<R> {
+        &self.debugger
+    }
+
+    pub fn into_inner(self) -> Debugger<R> {
+        self.debugger
+    }
+
+    // Serves the first client connecting to `listener`.
+    pub fn serve(&mut self, listener: &TcpListener) -> io::Result<()> {
+        let (stream, _) = listener.accept()?;
+
+        self.handle(stream)
+    }
+
+    // Answers packets until the client detaches, kills the program or disconnects.
+    pub fn handle(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        self.no_ack = false;
+
+        while let Some(packet) = read_packet(&mut stream)? {
+            if !self.no_ack {
+                stream.write_all(b"+")?;
+            }
+
+            let Ok(packet) = core::str::from_utf8(&packet) else {
+                self.reply(&mut stream, "E01")?;
+                continue;
+            };
+
+            match packet {
+                "D" => return self.reply(&mut stream, "OK"),
+                "k" => return Ok(()),
+                "c" => {
+                    let reply = self.resume(&mut stream)?;
+                    self.reply(&mut stream, &reply)?;
+                }
+                "s" => {
+                    let reply = match self.debugger.step() {
+                        Ok(StopReason::Halted) => String::from("W00"),
+                        Ok(_) => format!("S{:02x}", SIGTRAP),
+                        Err(_) => format!("S{:02x}", SIGILL),
+                    };
+                    self.reply(&mut stream, &reply)?;
+                }
+                "QStartNoAckMode" => {
+                    self.reply(&mut stream, "OK")?;
+                    self.no_ack = true;
+                }
+                _ => {
+                    let reply = self.answer(packet).unwrap_or_else(|| String::from("E01"));
+                    self.reply(&mut stream, &reply)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs frame after frame until the program stops or the client interrupts it.
+    fn resume(&mut self, stream: &mut TcpStream) -> io::Result<String> {
+        loop {
+            let reason = match self.debugger.run_frame() {
+                Ok(reason) => reason,
+                Err(_) => return Ok(format!("S{:02x}", SIGILL)),
+            };
+
+            match reason {
+                StopReason::FrameEnd => {
+                    if interrupted(stream)? {
+                        return Ok(format!("S{:02x}", SIGINT));
+                    }
+                }
+                StopReason::Halted => return Ok(String::from("W00")),
+                StopReason::Watchpoint(id) => {
+                    let watch = self.watchpoints.iter().find(|watch| watch.3 == id);
+
+                    return Ok(match watch {
+                        Some(&(kind, address, ..)) => {
+                            let name = match kind {
+                                2 => "watch",
+                                3 => "rwatch",
+                                _ => "awatch",
+                            };
+
+                            format!("T{:02x}{}:{:x};", SIGTRAP, name, address)
+                        }
+                        None => format!("S{:02x}", SIGTRAP),
+                    });
+                }
+                _ => return Ok(format!("S{:02x}", SIGTRAP)),
+            }
+        }
+    }
+
+    // Answers every packet that doesn't run the program, `None` for malformed packets.
+    fn answer(&mut self, packet: &str) -> Option<String> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => REGISTERS
+                .iter()
+                .map(|&(register, _, size)| self.read_register(register, size))
+                .collect(),
+            "G" => {
+                let mut bytes = decode_hex(args)?.into_iter();
+
+                for &(register, _, size) in REGISTERS.iter() {
+                    let value = (0..size).try_fold(0u16, |value, i| {
+                        Some(value | (bytes.next()? as u16) << (8 * i))
+                    })?;
+
+                    self.debugger.set_register(register, value);
+                }
+
+                String::from("OK")
+            }
+            "p" => {
+                let (register, _, size) = *REGISTERS.get(usize::from_str_radix(args, 16).ok()?)?;
+
+                self.read_register(register, size)
+            }
+            "P" => {
+                let (number, value) = args.split_once('=')?;
+                let (register, _, _) = *REGISTERS.get(usize::from_str_radix(number, 16).ok()?)?;
+                let value = decode_hex(value)?
+                    .iter()
+                    .enumerate()
+                    .fold(0u16, |value, (i, &byte)| value | (byte as u16) << (8 * i));
+
+                self.debugger.set_register(register, value);
+
+                String::from("OK")
+            }
+            "m" => {
+                let (address, len) = parse_range(args)?;
+
+                self.debugger
+                    .memory()
+                    .get(address..address.checked_add(len)?)?
+                    .iter()
+                    .fold(String::new(), |mut hex, byte| {
+                        hex.push_str(&format!("{:02x}", byte));
+                        hex
+                    })
+            }
+            "M" => {
+                let (range, data) = args.split_once(':')?;
+                let (address, len) = parse_range(range)?;
+                let data = decode_hex(data)?;
+
+                if data.len() != len {
+                    return None;
+                }
+
+                self.debugger
+                    .memory_mut()
+                    .get_mut(address..address.checked_add(len)?)?
+                    .copy_from_slice(&data);
+
+                String::from("OK")
+            }
+            "Z" | "z" => {
+                let mut fields = args.splitn(3, ',');
+                let kind: u8 = fields.next()?.parse().ok()?;
+                let address = u16::from_str_radix(fields.next()?, 16).ok()?;
+                let len = u16::from_str_radix(fields.next()?, 16).ok()?;
+
+                self.set_point(command == "Z", kind, address, len)?
+            }
+            "H" => String::from("OK"),
+            "q" => self.query(args)?,
+            // Unsupported packets get an empty reply.
+            _ => String::new(),
+        };
+
+        Some(reply)
+    }
+
+    fn query(&self, query: &str) -> Option<String> {
+        let reply = if query.starts_with("Supported") {
+            String::from("PacketSize=4000;QStartNoAckMode+;qXfer:features:read+")
+        } else if let Some(args) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let (offset, len) = parse_range(args)?;
+            let xml = target_xml();
+            let end = offset.saturating_add(len);
+            let chunk = xml.get(offset.min(xml.len())..end.min(xml.len()))?;
+
+            format!("{}{}", if end >= xml.len() { "l" } else { "m" }, chunk)
+        } else {
+            match query {
+                "Attached" => String::from("1"),
+                "C" => String::from("QC1"),
+                "fThreadInfo" => String::from("m1"),
+                "sThreadInfo" => String::from("l"),
+                _ => String::new(),
+            }
+        };
+
+        Some(reply)
+    }
+
+    fn set_point(&mut self, insert: bool, kind: u8, address: u16, len: u16) -> Option<String> {
+        let access = match kind {
+            // Software and hardware breakpoints are the same thing here.
+            0 | 1 => {
+                if insert {
+                    self.debugger.set_breakpoint(address, None);
+                } else {
+                    self.debugger.remove_breakpoint(address);
+                }
+
+                return Some(String::from("OK"));
+            }
+            2 => Some(Access::Write),
+            3 => Some(Access::Read),
+            4 => None,
+            _ => return Some(String::new()),
+        };
+
+        if insert {
+            let id = self.debugger.add_watchpoint(Watchpoint::Memory {
+                address,
+                len,
+                access,
+            });
+
+            self.watchpoints.push((kind, address, len, id));
+        } else {
+            let position = self
+                .watchpoints
+                .iter()
+                .position(|watch| watch.0 == kind && watch.1 == address && watch.2 == len)?;
+            let (.., id) = self.watchpoints.remove(position);
+
+            self.debugger.remove_watchpoint(id);
+        }
+
+        Some(String::from("OK"))
+    }
+
+    fn read_register(&self, register: Register, size: usize) -> String {
+        let value = self.debugger.register(register);
+
+        (0..size)
+            .map(|i| format!("{:02x}", (value >> (8 * i)) as u8))
+            .collect()
+    }
+
+    fn reply(&self, stream: &mut TcpStream, data: &str) -> io::Result<()> {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+
+        stream.write_all(format!("${}#{:02x}", data, checksum).as_bytes())?;
+
+        if !self.no_ack {
+            // The client acknowledges with '+' or asks for a retransmission with '-'.
+            let mut ack = [0];
+
+            loop {
+                stream.read_exact(&mut ack)?;
+
+                match ack[0] {
+                    b'+' => break,
+                    b'-' => stream.write_all(format!("${}#{:02x}", data, checksum).as_bytes())?,
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Reads the next `$data#checksum` packet, asking for retransmission of corrupted ones. Returns
+// `None` once the client disconnected.
+fn read_packet(stream: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+    let mut byte = [0];
+
+    loop {
+        // Skip acknowledgements and interrupts received while stopped.
+        loop {
+            match stream.read(&mut byte)? {
+                0 => return Ok(None),
+                _ if byte[0] == b'$' => break,
+                _ => (),
+            }
+        }
+
+        let mut data = Vec::new();
+
+        loop {
+            stream.read_exact(&mut byte)?;
+
+            match byte[0] {
+                b'#' => break,
+                // Escaped byte.
+                b'}' => {
+                    stream.read_exact(&mut byte)?;
+                    data.push(byte[0] ^ 0x20);
+                }
+                byte => data.push(byte),
+            }
+        }
+
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum)?;
+
+        let expected = core::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+        // Checksums are computed over the data as sent, escapes included.
+        let sum = data.iter().fold(0u8, |sum, &byte| match byte {
+            b'#' | b'$' | b'}' | b'*' => sum.wrapping_add(b'}').wrapping_add(byte ^ 0x20),
+            _ => sum.wrapping_add(byte),
+        });
+
+        if expected == Some(sum) {
+            return Ok(Some(data));
+        }
+
+        stream.write_all(b"-")?;
+    }
+}
+
+// Checks for the 0x03 byte a client sends to interrupt a running program.
+fn interrupted(stream: &mut TcpStream) -> io::Result<bool> {
+    let mut byte = [0];
+
+    stream.set_nonblocking(true)?;
+    let result = stream.read(&mut byte);
+    stream.set_nonblocking(false)?;
+
+    match result {
+        Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+        Ok(_) => Ok(byte[0] == 0x03),
+        Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (address, len) = range.split_once(',')?;
+
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn target_xml() -> String {
+    let registers: String = REGISTERS
+        .iter()
+        .map(|&(register, name, size)| {
+            let kind = match register {
+                Register::Pc => "code_ptr",
+                Register::I => "data_ptr",
+                _ => "uint8",
+            };
+
+            format!(
+                "<reg name=\"{}\" bitsize=\"{}\" type=\"{}\"/>",
+                name,
+                size * 8,
+                kind
+            )
+        })
+        .collect();
+
+    format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.chip8.core\">{}</feature></target>",
+        registers
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GdbServer;
+    use crate::debugger::Debugger;
+    use crate::interpreter::Interpreter;
+    use std::format;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::string::String;
+    use std::thread;
+
+    // Scripted GDB client talking to a server on localhost.
+    struct Client(TcpStream);
+
+    impl Client {
+        fn send(&mut self, data: &str) -> String {
+            let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+            write!(self.0, "${}#{:02x}", data, checksum).unwrap();
+
+            let mut byte = [0];
+            self.0.read_exact(&mut byte).unwrap();
+            assert_eq!(byte[0], b'+');
+
+            self.receive()
+        }
+
+        fn receive(&mut self) -> String {
+            let mut byte = [0];
+
+            loop {
+                self.0.read_exact(&mut byte).unwrap();
+
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+
+            let mut reply = String::new();
+
+            loop {
+                self.0.read_exact(&mut byte).unwrap();
+
+                if byte[0] == b'#' {
+                    break;
+                }
+
+                reply.push(byte[0] as char);
+            }
+
+            let mut checksum = [0; 2];
+            self.0.read_exact(&mut checksum).unwrap();
+            self.0.write_all(b"+").unwrap();
+
+            reply
+        }
+
+        fn send_no_ack(&mut self, data: &str) -> String {
+            let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+            write!(self.0, "${}#{:02x}", data, checksum).unwrap();
+
+            self.receive_no_ack()
+        }
+
+        fn receive_no_ack(&mut self) -> String {
+            let mut reply = String::new();
+            let mut byte = [0];
+
+            loop {
+                self.0.read_exact(&mut byte).unwrap();
+
+                match byte[0] {
+                    b'$' => reply.clear(),
+                    b'#' => break,
+                    byte => reply.push(byte as char),
+                }
+            }
+
+            let mut checksum = [0; 2];
+            self.0.read_exact(&mut checksum).unwrap();
+
+            reply
+        }
+    }
+
+    fn session(program: &[u8], script: impl FnOnce(&mut Client) + Send + 'static) -> Interpreter {
+        let mut interpreter = Interpreter::default();
+        interpreter.load(program).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut client = Client(TcpStream::connect(address).unwrap());
+            script(&mut client);
+        });
+
+        let mut server = GdbServer::new(Debugger::new(interpreter));
+        server.serve(&listener).unwrap();
+        client.join().unwrap();
+
+        server.into_inner().into_inner()
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        // LD V0, 0x12; LD I, 0x345
+        let interpreter = session(&[0x60, 0x12, 0xA3, 0x45], |client| {
+            assert_eq!(client.send("?"), "S05");
+            assert_eq!(client.send("s"), "S05");
+            assert_eq!(client.send("s"), "S05");
+
+            let registers = client.send("g");
+            assert_eq!(&registers[..2], "12");
+            assert_eq!(&registers[32..40], "45030402");
+
+            assert_eq!(client.send("p11"), "0402");
+            assert_eq!(client.send("P1=34"), "OK");
+            assert_eq!(client.send("m200,4"), "6012a345");
+            assert_eq!(client.send("M300,2:beef"), "OK");
+            assert_eq!(client.send("m300,2"), "beef");
+            assert_eq!(client.send("m1000,1"), "E01");
+            assert_eq!(client.send("D"), "OK");
+        });
+
+        assert_eq!(interpreter.v[1], 0x34);
+        assert_eq!(interpreter.memory[0x300..0x302], [0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn test_hostile_packets() {
+        session(&[0x60, 0x12], |client| {
+            assert_eq!(client.send("mffffffffffffffff,10"), "E01");
+            assert_eq!(client.send("m10,ffffffffffffffff"), "E01");
+            assert_eq!(client.send("Mffffffffffffffff,1:ff"), "E01");
+            assert_eq!(client.send("m200,fffffffffffffe00"), "E01");
+            assert_eq!(
+                client.send("qXfer:features:read:target.xml:ffffffffffffffff,10"),
+                "l"
+            );
+            assert!(client
+                .send("qXfer:features:read:target.xml:1,ffffffffffffffff")
+                .starts_with("l?xml"));
+            assert_eq!(client.send("D"), "OK");
+        });
+    }
+
+    #[test]
+    fn test_breakpoints_and_continue() {
+        // Counts V0 up forever.
+        session(&[0x70, 0x01, 0x12, 0x00], |client| {
+            assert_eq!(client.send("QStartNoAckMode"), "OK");
+            assert_eq!(client.send_no_ack("Z0,202,2"), "OK");
+            assert_eq!(client.send_no_ack("c"), "S05");
+            assert_eq!(client.send_no_ack("p0"), "01");
+            assert_eq!(client.send_no_ack("c"), "S05");
+            assert_eq!(client.send_no_ack("p0"), "02");
+            assert_eq!(client.send_no_ack("z0,202,2"), "OK");
+
+            // Runs until interrupted.
+            write!(client.0, "$c#63").unwrap();
+            client.0.write_all(&[0x03]).unwrap();
+            assert_eq!(client.receive_no_ack(), "S02");
+
+            client.0.write_all(b"$k#6b").unwrap();
+        });
+    }
+
+    #[test]
+    fn test_watchpoint_and_exit() {
+        // LD I, 0x300; LD V0, 123; LD B, V0; EXIT
+        session(
+            &[0xA3, 0x00, 0x60, 0x7B, 0xF0, 0x33, 0x00, 0xFD],
+            |client| {
+                assert_eq!(client.send("Z2,301,1"), "OK");
+                assert_eq!(client.send("c"), format!("T05watch:{:x};", 0x301));
+                assert_eq!(client.send("z2,301,1"), "OK");
+                assert_eq!(client.send("c"), "W00");
+
+                client.0.write_all(b"$k#6b").unwrap();
+            },
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use crate::framebuffer::{Framebuffer, Region};
+use crate::interpreter::{ExecError, Interpreter, InterpreterEvent};
+use crate::random::RandomSource;
+#[cfg(feature = "alloc")]
+use crate::rewind::Rewind;
+
+// Receives the screen once per frame in which it changed.
+pub trait Display {
+    // `region` covers every pixel changed since the last call, the whole screen after a
+    // resolution change.
+    fn frame_ready(&mut self, screen: &Framebuffer, region: Region);
+}
+
+// Notified on the edges of the buzzer, which sounds while the sound timer is active.
+pub trait Audio {
+    // XO-CHIP programs play `pattern` at `pitch`, see `Interpreter::pitch`.
+    fn buzzer_on(&mut self, _pattern: &[u8; 16], _pitch: u8) {}
+
+    fn buzzer_off(&mut self) {}
+}
+
+// Polled for the state of the hex keypad at the start of every frame.
+pub trait Input {
+    fn is_key_pressed(&mut self, key: u8) -> bool;
+}
+
+impl<R: RandomSource> Interpreter<R> {
+    // Runs one frame, taking the keypad from `host` and reporting screen and buzzer changes
+    // back to it.
+    pub fn run_frame_with<H>(&mut self, host: &mut H) -> Result<InterpreterEvent, ExecError>
+    where
+        H: Display + Audio + Input,
+    {
+        self.poll_input(host);
+        let event = self.run_frame();
+        self.present(host);
+
+        event
+    }
+
+    // Reports pending screen and buzzer changes, e.g. after restoring a snapshot.
+    pub fn present<H: Display + Audio>(&mut self, host: &mut H) {
+        if let Some(region) = self.screen.take_dirty() {
+            host.frame_ready(&self.screen, region);
+        }
+
+        let buzzer = self.sound_timer > 0 && !self.halted;
+
+        if buzzer != self.buzzer {
+            self.buzzer = buzzer;
+
+            if buzzer {
+                host.buzzer_on(&self.audio_pattern, self.pitch);
+            } else {
+                host.buzzer_off();
+            }
+        }
+    }
+
+    pub(crate) fn poll_input<I: Input>(&mut self, input: &mut I) {
+        for key in 0..16 {
+            match (input.is_key_pressed(key), self.is_key_pressed(key)) {
+                (true, false) => self.press_key(key),
+                (false, true) => self.release_key(key),
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<R: RandomSource> Rewind<R> {
+    pub fn run_frame_with<H>(&mut self, host: &mut H) -> Result<InterpreterEvent, ExecError>
+    where
+        H: Display + Audio + Input,
+    {
+        self.interpreter.poll_input(host);
+        let event = self.run_frame();
+        self.interpreter.present(host);
+
+        event
+    }
+
+    pub fn present<H: Display + Audio>(&mut self, host: &mut H) {
+        self.interpreter.present(host);
+    }
+}
+
+// Host without any output, for tests and tools running programs without a window.
+//
+// Keeps a copy of the last presented screen and counts what it was told.
+#[derive(Clone, Default)]
+pub struct Headless {
+    pub keys: [bool; 16],
+    pub screen: Option<Framebuffer>,
+    pub frames_presented: usize,
+    pub buzzer: bool,
+    pub buzzer_edges: usize,
+}
+
+impl Display for Headless {
+    fn frame_ready(&mut self, screen: &Framebuffer, _region: Region) {
+        self.screen = Some(screen.clone());
+        self.frames_presented += 1;
+    }
+}
+
+impl Audio for Headless {
+    fn buzzer_on(&mut self, _pattern: &[u8; 16], _pitch: u8) {
+        self.buzzer = true;
+        self.buzzer_edges += 1;
+    }
+
+    fn buzzer_off(&mut self) {
+        self.buzzer = false;
+        self.buzzer_edges += 1;
+    }
+}
+
+impl Input for Headless {
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::Headless;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_headless_screen() {
+        let mut interpreter = Interpreter::default();
+        let mut host = Headless::default();
+
+        // Draws the 0 glyph and loops.
+        interpreter.load(&[0xD0, 0x05, 0x12, 0x02]).unwrap();
+        interpreter.run_frame_with(&mut host).unwrap();
+        assert_eq!(host.frames_presented, 1);
+
+        let screen = host.screen.as_ref().unwrap();
+        assert_eq!(screen.pixel(0, 0), 1);
+
+        // Nothing changed, nothing presented.
+        interpreter.run_frame_with(&mut host).unwrap();
+        assert_eq!(host.frames_presented, 1);
+    }
+
+    #[test]
+    fn test_headless_buzzer_edges() {
+        let mut interpreter = Interpreter::default();
+        let mut host = Headless::default();
+
+        // ST = 2, loop.
+        interpreter
+            .load(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04])
+            .unwrap();
+        interpreter.run_frame_with(&mut host).unwrap();
+        assert!(host.buzzer);
+
+        interpreter.run_frame_with(&mut host).unwrap();
+        assert!(!host.buzzer);
+        assert_eq!(host.buzzer_edges, 2);
+    }
+
+    #[test]
+    fn test_headless_input() {
+        let mut interpreter = Interpreter::default();
+        let mut host = Headless::default();
+
+        // Waits for key and stores it in V1.
+        interpreter.load(&[0xF1, 0x0A, 0x12, 0x02]).unwrap();
+        interpreter.run_frame_with(&mut host).unwrap();
+        assert!(interpreter.is_waiting_for_key());
+
+        host.keys[0xA] = true;
+        interpreter.run_frame_with(&mut host).unwrap();
+        host.keys[0xA] = false;
+        interpreter.run_frame_with(&mut host).unwrap();
+
+        assert!(!interpreter.is_waiting_for_key());
+        assert_eq!(interpreter.v[1], 0xA);
+    }
+}
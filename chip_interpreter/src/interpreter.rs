@@ -1,32 +1,353 @@
-use rand::random;
+use crate::framebuffer::Framebuffer;
+use crate::quirks::Quirks;
+use crate::random::{RandomSource, Xorshift};
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+use core::ops::Range;
 
+pub const MAX_MEMORY: usize = 0x10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterpreterEvent {
     Audio,
+    Exit,
     Opcode(u16),
 }
 
-pub struct Interpreter {
-    pc: u16,
-    index: u16,
-    sp: u8,
-    delay_timer: u8,
-    sound_timer: u8,
-    v: [u8; 16],
-    stack: [u16; 16],
-    memory: [u8; 4096],
-    pub screen_buffer: [u8; 64 * 32],
+#[derive(Clone)]
+pub struct Interpreter<R = Xorshift> {
+    pub(crate) pc: u16,
+    pub(crate) index: u16,
+    pub(crate) sp: u8,
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) v: [u8; 16],
+    pub(crate) stack: [u16; 16],
+    pub(crate) memory: [u8; MAX_MEMORY],
+    pub(crate) memory_size: usize,
+    pub(crate) keypad: [bool; 16],
+    pub(crate) key_wait: KeyWait,
+    pub(crate) instructions_per_frame: u32,
+    pub(crate) quirks: Quirks,
+    pub(crate) vblank_wait: bool,
+    pub(crate) halted: bool,
+    pub(crate) rpl: [u8; 16],
+    pub(crate) audio_pattern: [u8; 16],
+    pub(crate) pitch: u8,
+    pub(crate) screen: Framebuffer,
+    pub(crate) random: R,
+    pub(crate) buzzer: bool,
+    pub(crate) last_access: Option<(Access, Range<usize>)>,
+}
+
+// Kind of data memory access made by an instruction, opcode fetches aren't counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
 }
 
-impl Interpreter {
-    pub fn load(&mut self, program: &[u8]) {
-        *self = Default::default();
-        self.memory[self.pc as usize..self.pc as usize + program.len()].copy_from_slice(program);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum KeyWait {
+    Idle,
+    Waiting,
+    Pressed(u8),
+    Released(u8),
+}
+
+impl<R> Interpreter<R> {
+    // Uses `random` as the source for CXNN, e.g. a `Xorshift` with a fixed seed or `Scripted`
+    // values to make runs reproducible.
+    pub fn with_random(random: R) -> Self {
+        let mut memory = [0; MAX_MEMORY];
+        memory[0..FONT.len()].copy_from_slice(&FONT);
+        memory[FONT.len()..FONT.len() + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+
+        Self {
+            pc: 0x200,
+            index: 0x200,
+            memory,
+            memory_size: 0x1000,
+            sp: Default::default(),
+            delay_timer: Default::default(),
+            sound_timer: Default::default(),
+            v: Default::default(),
+            stack: Default::default(),
+            keypad: Default::default(),
+            key_wait: KeyWait::Idle,
+            instructions_per_frame: 11,
+            quirks: Quirks::default(),
+            vblank_wait: false,
+            halted: false,
+            rpl: Default::default(),
+            audio_pattern: Default::default(),
+            pitch: 64,
+            screen: Default::default(),
+            random,
+            buzzer: false,
+            last_access: None,
+        }
+    }
+
+    // Copies the machine state of `other`, keeping the keypad, the random source and the host
+    // configuration of `self`.
+    pub(crate) fn copy_state<S>(&mut self, other: &Interpreter<S>) {
+        self.pc = other.pc;
+        self.index = other.index;
+        self.sp = other.sp;
+        self.delay_timer = other.delay_timer;
+        self.sound_timer = other.sound_timer;
+        self.v = other.v;
+        self.stack = other.stack;
+        self.memory = other.memory;
+        self.memory_size = other.memory_size;
+        self.key_wait = other.key_wait;
+        self.vblank_wait = other.vblank_wait;
+        self.halted = other.halted;
+        self.rpl = other.rpl;
+        self.audio_pattern = other.audio_pattern;
+        self.pitch = other.pitch;
+        self.screen = other.screen.clone();
+        self.screen.mark_all_dirty();
+    }
+}
+
+impl<R: RandomSource> Interpreter<R> {
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    // 4 KiB for CHIP-8 and SUPER-CHIP, XO-CHIP programs address up to 64 KiB.
+    pub fn with_memory_size(mut self, memory_size: usize) -> Self {
+        self.set_memory_size(memory_size);
+        self
+    }
+
+    pub fn set_memory_size(&mut self, memory_size: usize) {
+        self.memory_size = memory_size.clamp(0x1000, MAX_MEMORY);
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    pub fn with_instructions_per_frame(mut self, instructions_per_frame: u32) -> Self {
+        self.set_instructions_per_frame(instructions_per_frame);
+        self
     }
 
-    pub fn cycle(&mut self) -> InterpreterEvent {
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    pub fn load(&mut self, program: &[u8]) -> Result<(), ExecError> {
+        self.reset();
+
+        let start = self.pc as usize;
+        let end = start + program.len();
+
+        if end > self.memory_size {
+            return Err(ExecError::ProgramTooLarge(program.len()));
+        }
+
+        self.memory[start..end].copy_from_slice(program);
+
+        Ok(())
+    }
+
+    // Puts the machine back in its power-on state, keeping the host configuration. Done in
+    // place as the memory makes a whole `Interpreter` too large for a temporary.
+    fn reset(&mut self) {
+        self.pc = 0x200;
+        self.index = 0x200;
+        self.sp = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.v = Default::default();
+        self.stack = Default::default();
+        self.memory.fill(0);
+        self.memory[0..FONT.len()].copy_from_slice(&FONT);
+        self.memory[FONT.len()..FONT.len() + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+        self.keypad = Default::default();
+        self.key_wait = KeyWait::Idle;
+        self.vblank_wait = false;
+        self.halted = false;
+        self.rpl = Default::default();
+        self.audio_pattern = Default::default();
+        self.pitch = 64;
+        self.screen = Default::default();
+        self.screen.mark_all_dirty();
+        self.last_access = None;
+    }
+
+    pub fn press_key(&mut self, key: u8) {
+        let key = key & 0xF;
+
+        self.keypad[key as usize] = true;
+
+        if self.key_wait == KeyWait::Waiting {
+            self.key_wait = KeyWait::Pressed(key);
+        }
+    }
+
+    pub fn release_key(&mut self, key: u8) {
+        let key = key & 0xF;
+
+        self.keypad[key as usize] = false;
+
+        if self.key_wait == KeyWait::Pressed(key) {
+            self.key_wait = KeyWait::Released(key);
+        }
+    }
+
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        self.keypad[(key & 0xF) as usize]
+    }
+
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait != KeyWait::Idle
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn screen(&self) -> &Framebuffer {
+        &self.screen
+    }
+
+    // The 128 one bit samples played while the sound timer is active.
+    pub fn audio_pattern(&self) -> &[u8; 16] {
+        &self.audio_pattern
+    }
+
+    // Playback rate of the audio pattern is 4000 * 2 ^ ((pitch - 64) / 48) Hz.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    // Runs one 60 Hz frame: `instructions_per_frame` instructions followed by a timer tick.
+    pub fn run_frame(&mut self) -> Result<InterpreterEvent, ExecError> {
+        let mut opcode = self.execute()?;
+
+        for _ in 1..self.instructions_per_frame {
+            if self.halted {
+                break;
+            }
+
+            opcode = self.execute()?;
+        }
+
+        self.tick_timers();
+
+        Ok(self.event(opcode))
+    }
+
+    // Decrements the delay and sound timers, must be called at 60 Hz.
+    pub fn tick_timers(&mut self) {
+        self.vblank_wait = false;
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+    }
+
+    pub fn cycle(&mut self) -> Result<InterpreterEvent, ExecError> {
+        let opcode = self.execute()?;
+
+        Ok(self.event(opcode))
+    }
+
+    pub(crate) fn event(&self, opcode: u16) -> InterpreterEvent {
+        if self.halted {
+            InterpreterEvent::Exit
+        } else if self.sound_timer > 0 {
+            // Play the beep sound when sound_timer > 0
+            InterpreterEvent::Audio
+        } else {
+            InterpreterEvent::Opcode(opcode)
+        }
+    }
+
+    // Bounds checks the `len` bytes starting at the index register and records the access.
+    fn memory_range(
+        &mut self,
+        address: u16,
+        len: usize,
+        access: Access,
+    ) -> Result<Range<usize>, ExecError> {
+        let start = self.index as usize;
+        let end = start + len;
+
+        if end > self.memory_size {
+            return Err(ExecError::MemoryOutOfBounds {
+                address,
+                index: self.index,
+                len,
+            });
+        }
+
+        self.last_access = Some((access, start..end));
+
+        Ok(start..end)
+    }
+
+    // Distance to the next instruction, skipping over it when `condition` holds.
+    fn skip(&self, condition: bool) -> u16 {
+        if !condition {
+            return 2;
+        }
+
+        2 + self.instruction_len(self.pc as usize + 2)
+    }
+
+    // Size of the instruction at `address`, F000 NNNN is the only one that is four bytes long.
+    pub(crate) fn instruction_len(&self, address: usize) -> u16 {
+        match self.memory.get(address..address + 2) {
+            Some([0xF0, 0x00]) => 4,
+            _ => 2,
+        }
+    }
+
+    // Moves the program counter `by` bytes forward, there is nothing past the end of 64 KiB.
+    fn advance(&mut self, by: u16) -> Result<(), ExecError> {
+        self.pc = self
+            .pc
+            .checked_add(by)
+            .ok_or(ExecError::PcOutOfRange(self.pc))?;
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(&mut self) -> Result<u16, ExecError> {
+        let address = self.pc;
+
+        self.last_access = None;
+
+        if address as usize + 1 >= self.memory_size {
+            return Err(ExecError::PcOutOfRange(address));
+        }
+
         let opcode = u16::from_be_bytes([
-            self.memory[self.pc as usize],
-            self.memory[(self.pc + 1) as usize],
+            self.memory[address as usize],
+            self.memory[address as usize + 1],
         ]);
 
         #[inline(always)]
@@ -55,15 +376,51 @@ impl Interpreter {
         }
 
         match opcode {
+            // SCD N | 00CN
+            0x00C0..=0x00CF => {
+                self.screen.scroll_down(n(opcode));
+                self.advance(2)?;
+            }
+
             0x00E0 => {
-                self.screen_buffer = [0; 64 * 32];
-                self.pc += 2;
+                self.screen.clear();
+                self.advance(2)?;
             }
 
             0x00EE => {
-                self.sp -= 1;
+                self.sp = self
+                    .sp
+                    .checked_sub(1)
+                    .ok_or(ExecError::StackUnderflow(address))?;
                 self.pc = self.stack[self.sp as usize];
-                self.pc += 2;
+                self.advance(2)?;
+            }
+
+            // SCR | 00FB
+            0x00FB => {
+                self.screen.scroll_right(4);
+                self.advance(2)?;
+            }
+
+            // SCL | 00FC
+            0x00FC => {
+                self.screen.scroll_left(4);
+                self.advance(2)?;
+            }
+
+            // EXIT | 00FD
+            0x00FD => self.halted = true,
+
+            // LOW | 00FE
+            0x00FE => {
+                self.screen.set_hires(false);
+                self.advance(2)?;
+            }
+
+            // HIGH | 00FF
+            0x00FF => {
+                self.screen.set_hires(true);
+                self.advance(2)?;
             }
 
             // JMP NNN | 1NNN
@@ -71,6 +428,10 @@ impl Interpreter {
 
             // CALL NNN | 2NNN
             0x2000..=0x2FFF => {
+                if self.sp as usize >= self.stack.len() {
+                    return Err(ExecError::StackOverflow(address));
+                }
+
                 self.stack[self.sp as usize] = self.pc;
                 self.sp += 1;
                 self.pc = nnn(opcode);
@@ -81,7 +442,7 @@ impl Interpreter {
                 let x = x(opcode);
                 let data = nn(opcode);
 
-                self.pc += if self.v[x] == data { 4 } else { 2 };
+                self.advance(self.skip(self.v[x] == data))?;
             }
 
             // JNE VX, NN | 4XNN
@@ -89,15 +450,54 @@ impl Interpreter {
                 let x = x(opcode);
                 let data = nn(opcode);
 
-                self.pc += if self.v[x] != data { 4 } else { 2 };
+                self.advance(self.skip(self.v[x] != data))?;
             }
 
             // JEQ VX, VY | 5XY0
-            0x5000..=0x5FF0 => {
+            _ if opcode & 0xF00F == 0x5000 => {
                 let x = x(opcode);
                 let y = y(opcode);
 
-                self.pc += if self.v[x] == self.v[y] { 4 } else { 2 };
+                self.advance(self.skip(self.v[x] == self.v[y]))?;
+            }
+
+            // SAVE VX, VY | 5XY2
+            _ if opcode & 0xF00F == 0x5002 => {
+                let (x, y) = (x(opcode), y(opcode));
+                let range = self.memory_range(address, x.abs_diff(y) + 1, Access::Write)?;
+
+                if x <= y {
+                    self.memory[range].copy_from_slice(&self.v[x..=y]);
+                } else {
+                    let registers = self.v[y..=x].iter().rev();
+
+                    self.memory[range]
+                        .iter_mut()
+                        .zip(registers)
+                        .for_each(|(m, v)| *m = *v);
+                }
+
+                self.advance(2)?;
+            }
+
+            // LOAD VX, VY | 5XY3
+            _ if opcode & 0xF00F == 0x5003 => {
+                let (x, y) = (x(opcode), y(opcode));
+                let range = self.memory_range(address, x.abs_diff(y) + 1, Access::Read)?;
+
+                if x <= y {
+                    self.v[x..=y].copy_from_slice(&self.memory[range]);
+                } else {
+                    let memory = self.memory[range].iter();
+
+                    self.v[y..=x]
+                        .iter_mut()
+                        .rev()
+                        .zip(memory)
+                        .for_each(|(v, m)| *v = *m);
+                }
+
+                self.advance(2)?;
             }
 
             // MOV VX, NN | 6XNN
@@ -106,211 +506,315 @@ impl Interpreter {
                 let data = nn(opcode);
 
                 self.v[x] = data;
-                self.pc += 2;
+                self.advance(2)?;
+            }
+
+            // ADD VX, NN | 7XNN
+            0x7000..=0x7FFF => {
+                let x = x(opcode);
+                let data = nn(opcode);
+
+                self.v[x] = self.v[x].wrapping_add(data);
+                self.advance(2)?;
             }
 
             // MOV VX, VY | 8XY0
-            0x8000..=0x8FF0 => {
+            _ if opcode & 0xF00F == 0x8000 => {
                 self.v[x(opcode)] = self.v[y(opcode)];
-                self.pc += 2;
+                self.advance(2)?;
             }
 
             // OR VX, VY | 8XY1
-            0x8001..=0x8FF1 => {
+            _ if opcode & 0xF00F == 0x8001 => {
                 self.v[x(opcode)] |= self.v[y(opcode)];
-                self.pc += 2;
+                self.reset_vf();
+                self.advance(2)?;
             }
 
             // AND VX, VY | 8XY2
-            0x8002..=0x8FF2 => {
+            _ if opcode & 0xF00F == 0x8002 => {
                 self.v[x(opcode)] &= self.v[y(opcode)];
-                self.pc += 2;
+                self.reset_vf();
+                self.advance(2)?;
             }
 
             // XOR VX, VY | 8XY3
-            0x8003..=0x8FF3 => {
+            _ if opcode & 0xF00F == 0x8003 => {
                 self.v[x(opcode)] ^= self.v[y(opcode)];
-                self.pc += 2;
+                self.reset_vf();
+                self.advance(2)?;
             }
 
             // ADC VX, VY | 8XY4
-            0x8004..=0x8FF4 => {
+            _ if opcode & 0xF00F == 0x8004 => {
                 let x = x(opcode);
                 let y = y(opcode);
 
                 let (result, overflow) = self.v[x].overflowing_add(self.v[y]);
 
-                self.v[0xF] = overflow as u8;
                 self.v[x] = result;
-                self.pc += 2;
+                self.v[0xF] = overflow as u8;
+                self.advance(2)?;
             }
 
             // SBC VX, VY | 8XY5
-            0x8005..=0x8FF5 => {
+            _ if opcode & 0xF00F == 0x8005 => {
                 let x = x(opcode);
                 let y = y(opcode);
 
-                let (result, overflow) = self.v[x].overflowing_sub(self.v[y]);
+                let (result, borrow) = self.v[x].overflowing_sub(self.v[y]);
 
-                self.v[0xF] = overflow as u8;
                 self.v[x] = result;
-                self.pc += 2;
+                self.v[0xF] = !borrow as u8;
+                self.advance(2)?;
             }
 
             // SHR VX | 8XY6
-            0x8006..=0x8FF6 => {
+            _ if opcode & 0xF00F == 0x8006 => {
                 let x = x(opcode);
-
-                self.v[0xF] = self.v[x] & 0x1;
-                self.v[x] >>= 1;
-                self.pc += 2;
+                let value = if self.quirks.shift {
+                    self.v[x]
+                } else {
+                    self.v[y(opcode)]
+                };
+
+                self.v[x] = value >> 1;
+                self.v[0xF] = value & 0x1;
+                self.advance(2)?;
             }
 
             // SUB VX, VY | 8XY7
-            0x8007..=0x8FF7 => {
+            _ if opcode & 0xF00F == 0x8007 => {
                 let x = x(opcode);
                 let y = y(opcode);
 
-                let (result, overflow) = self.v[y].overflowing_sub(self.v[x]);
+                let (result, borrow) = self.v[y].overflowing_sub(self.v[x]);
 
-                self.v[0xF] = overflow as u8;
                 self.v[x] = result;
-                self.pc += 2;
+                self.v[0xF] = !borrow as u8;
+                self.advance(2)?;
             }
 
             // SHL VX | 8XYE
-            0x800E..=0x8FFE => {
+            _ if opcode & 0xF00F == 0x800E => {
                 let x = x(opcode);
-
-                self.v[0xF] = (self.v[x] & 0x80) >> 7;
-                self.v[x] <<= 1;
-                self.pc += 2;
+                let value = if self.quirks.shift {
+                    self.v[x]
+                } else {
+                    self.v[y(opcode)]
+                };
+
+                self.v[x] = value << 1;
+                self.v[0xF] = (value & 0x80) >> 7;
+                self.advance(2)?;
             }
 
             // JNE VX, VY | 9XY0
-            0x9000..=0x9FF0 => {
+            _ if opcode & 0xF00F == 0x9000 => {
                 let x = x(opcode);
                 let y = y(opcode);
 
-                self.pc += if self.v[x] != self.v[y] { 4 } else { 2 };
+                self.advance(self.skip(self.v[x] != self.v[y]))?;
             }
 
             // LOAD I | ANNN
             0xA000..=0xAFFF => {
                 self.index = nnn(opcode);
-                self.pc += 2;
+                self.advance(2)?;
             }
 
             // JMP V0, NNN | BNNN
-            0xB000..=0xBFFF => self.pc = nnn(opcode) + self.v[0] as u16,
+            0xB000..=0xBFFF => {
+                let offset = if self.quirks.jump_with_vx {
+                    self.v[x(opcode)]
+                } else {
+                    self.v[0]
+                };
+
+                self.pc = nnn(opcode) + offset as u16;
+            }
 
             // RAND VX, NN | CXNN
             0xC000..=0xCFFF => {
                 let data = nn(opcode);
 
-                self.v[x(opcode)] = data & random::<u8>();
-                self.pc += 2;
+                self.v[x(opcode)] = data & self.random.next_u8();
+                self.advance(2)?;
             }
 
             // DRAW VX, VY, N | DXYN
+            // DRAW VX, VY, 0 | DXY0 draws a 16x16 sprite.
             0xD000..=0xDFFF => {
+                if self.quirks.display_wait && self.vblank_wait {
+                    return Ok(opcode);
+                }
+
                 let vx = self.v[x(opcode)] as usize;
                 let vy = self.v[y(opcode)] as usize;
-                let height = n(opcode);
+                let wide = n(opcode) == 0;
+                let planes = self.screen.planes().count_ones() as usize;
+                let size = if wide { 32 } else { n(opcode) };
+                let sprite = self.memory_range(address, size * planes, Access::Read)?;
+
+                let collision =
+                    self.screen
+                        .draw(vx, vy, &self.memory[sprite], wide, self.quirks.clipping);
+
+                self.v[0xF] = collision as u8;
+                self.vblank_wait = true;
+                self.advance(2)?;
+            }
 
-                self.v[0xF] = 0;
+            // KEY VX | EX9E
+            _ if opcode & 0xF0FF == 0xE09E => {
+                let key = self.v[x(opcode)];
 
-                for row in 0..height {
-                    let pixel = self.memory[self.index as usize + row];
+                self.advance(self.skip(self.is_key_pressed(key)))?;
+            }
 
-                    for col in 0..8 {
-                        if (pixel & (1 << (7 - col))) != 0 {
-                            let index = ((vy + row) % 32) * 64 + (vx + col) % 64;
-                            let read = &mut self.screen_buffer[index];
+            // KEYNOT VX | EXA1
+            _ if opcode & 0xF0FF == 0xE0A1 => {
+                let key = self.v[x(opcode)];
 
-                            self.v[0xF] |= *read & 1;
-                            *read ^= 1;
-                        }
-                    }
+                self.advance(self.skip(!self.is_key_pressed(key)))?;
+            }
+
+            // LOAD_LONG I, NNNN | F000 NNNN
+            0xF000 => {
+                if address as usize + 3 >= self.memory_size {
+                    return Err(ExecError::PcOutOfRange(address.saturating_add(2)));
                 }
 
-                self.pc += 2;
+                let next = address as usize + 2;
+
+                self.index = u16::from_be_bytes([self.memory[next], self.memory[next + 1]]);
+                self.advance(4)?;
             }
 
-            // KEY VX | EX9E
-            0xE09E..=0xEF9E => {
-                self.pc += 2; // TODO
+            // AUDIO | F002
+            0xF002 => {
+                let range = self.memory_range(address, 16, Access::Read)?;
+
+                self.audio_pattern.copy_from_slice(&self.memory[range]);
+                self.advance(2)?;
             }
 
-            // KEYNOT VX | EXA1
-            0xE0A1..=0xEFA1 => {
-                self.pc += 2; // TODO
+            // PLANE N | FN01
+            _ if opcode & 0xF0FF == 0xF001 => {
+                self.screen.select_planes(x(opcode) as u8);
+                self.advance(2)?;
             }
 
             // MOVDELAY VX | FX07
             _ if opcode & 0xF0FF == 0xF007 => {
                 self.v[x(opcode)] = self.delay_timer;
-                self.pc += 2;
+                self.advance(2)?;
             }
 
-            // WAITKEY | FX0A
-            _ if opcode & 0xF0FF == 0xF00A => {
-                self.pc += 2; // TODO
-            }
+            // WAITKEY VX | FX0A
+            // Blocks until a key is pressed and released again, like the COSMAC VIP.
+            _ if opcode & 0xF0FF == 0xF00A => match self.key_wait {
+                KeyWait::Released(key) => {
+                    self.v[x(opcode)] = key;
+                    self.key_wait = KeyWait::Idle;
+                    self.advance(2)?;
+                }
+                KeyWait::Idle => self.key_wait = KeyWait::Waiting,
+                KeyWait::Waiting | KeyWait::Pressed(_) => (),
+            },
 
             // SET_DELAY VX | FX15
             _ if opcode & 0xF0FF == 0xF015 => {
                 self.delay_timer = self.v[x(opcode)];
-                self.pc += 2;
+                self.advance(2)?;
             }
 
             // SETSOUND VX | FX18
             _ if opcode & 0xF0FF == 0xF018 => {
                 self.sound_timer = self.v[x(opcode)];
-                self.pc += 2;
+                self.advance(2)?;
             }
 
             // ADD_TO_INDEX Vx | FX1E
             _ if opcode & 0xF0FF == 0xF01E => {
-                self.index += self.v[x(opcode)] as u16;
-                self.pc += 2;
+                self.index = self.index.wrapping_add(self.v[x(opcode)] as u16);
+                self.advance(2)?;
             }
 
             // SET_SPRITE_ADDR Vx | FX29
             _ if opcode & 0xF0FF == 0xF029 => {
-                let character = self.v[x(opcode)];
+                let character = self.v[x(opcode)] & 0xF;
 
                 self.index = character as u16 * 5;
-                self.pc += 2;
+                self.advance(2)?;
+            }
+
+            // SET_BIG_SPRITE_ADDR VX | FX30
+            _ if opcode & 0xF0FF == 0xF030 => {
+                let character = self.v[x(opcode)] & 0xF;
+
+                self.index = (FONT.len() + character as usize * 10) as u16;
+                self.advance(2)?;
             }
 
             // STORE_BCD VX | FX33
             _ if opcode & 0xF0FF == 0xF033 => {
                 let value = self.v[x(opcode)];
+                let digits = self.memory_range(address, 3, Access::Write)?;
 
-                self.memory[self.index as usize] = value / 100;
-                self.memory[self.index as usize + 1] = (value / 10) % 10;
-                self.memory[self.index as usize + 2] = value % 10;
+                self.memory[digits].copy_from_slice(&[value / 100, (value / 10) % 10, value % 10]);
 
-                self.pc += 2;
+                self.advance(2)?;
             }
 
             // REG_DUMP [I] VX | FX55
             _ if opcode & 0xF0FF == 0xF055 => {
-                for index in 0..=x(opcode) {
-                    self.memory[self.index as usize + index] = self.v[index];
+                let x = x(opcode);
+                let range = self.memory_range(address, x + 1, Access::Write)?;
+
+                self.memory[range].copy_from_slice(&self.v[..=x]);
+
+                if self.quirks.load_store_increment {
+                    self.index = self.index.wrapping_add(x as u16 + 1);
                 }
 
-                self.pc += 2;
+                self.advance(2)?;
             }
 
             // REG_LOAD [I] VX | FX65
             _ if opcode & 0xF0FF == 0xF065 => {
-                for index in 0..=x(opcode) {
-                    self.v[index] = self.memory[self.index as usize + index];
+                let x = x(opcode);
+                let range = self.memory_range(address, x + 1, Access::Read)?;
+
+                self.v[..=x].copy_from_slice(&self.memory[range]);
+
+                if self.quirks.load_store_increment {
+                    self.index = self.index.wrapping_add(x as u16 + 1);
                 }
 
-                self.pc += 2;
+                self.advance(2)?;
+            }
+
+            // PITCH VX | FX3A
+            _ if opcode & 0xF0FF == 0xF03A => {
+                self.pitch = self.v[x(opcode)];
+                self.advance(2)?;
+            }
+
+            // SAVE_FLAGS VX | FX75
+            _ if opcode & 0xF0FF == 0xF075 => {
+                let x = x(opcode);
+
+                self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+                self.advance(2)?;
+            }
+
+            // LOAD_FLAGS VX | FX85
+            _ if opcode & 0xF0FF == 0xF085 => {
+                let x = x(opcode);
+
+                self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+                self.advance(2)?;
             }
 
             // REG_LOAD VX, I | FX65 ||NEW
@@ -322,19 +826,57 @@ impl Interpreter {
 
             //     self.pc += 2;
             // }
-            _ => panic!("Invalid opcode"),
+            _ => return Err(ExecError::UnknownOpcode { address, opcode }),
         }
 
-        if self.sound_timer > 0 {
-            // Play the beep sound when sound_timer > 0
-            self.sound_timer -= 1;
-            return InterpreterEvent::Audio;
-        } else {
-            InterpreterEvent::Opcode(opcode)
+        Ok(opcode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecError {
+    UnknownOpcode {
+        address: u16,
+        opcode: u16,
+    },
+    StackOverflow(u16),
+    StackUnderflow(u16),
+    MemoryOutOfBounds {
+        address: u16,
+        index: u16,
+        len: usize,
+    },
+    PcOutOfRange(u16),
+    ProgramTooLarge(usize),
+}
+
+impl Display for ExecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnknownOpcode { address, opcode } => {
+                write!(f, "Unknown opcode {:#06X} at {:#05X}", opcode, address)
+            }
+            Self::StackOverflow(address) => write!(f, "Stack overflow at {:#05X}", address),
+            Self::StackUnderflow(address) => write!(f, "Stack underflow at {:#05X}", address),
+            Self::MemoryOutOfBounds {
+                address,
+                index,
+                len,
+            } => write!(
+                f,
+                "Access of {} bytes at I = {:#05X} is out of bounds at {:#05X}",
+                len, index, address
+            ),
+            Self::PcOutOfRange(pc) => write!(f, "Program counter {:#05X} is out of range", pc),
+            Self::ProgramTooLarge(size) => {
+                write!(f, "Program of {} bytes doesn't fit into memory", size)
+            }
         }
     }
 }
 
+impl Error for ExecError {}
+
 const FONT: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -354,35 +896,52 @@ const FONT: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+const BIG_FONT: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+#[cfg(feature = "std")]
+impl From<Quirks> for Interpreter {
+    fn from(quirks: Quirks) -> Self {
+        Self::default().with_quirks(quirks)
+    }
+}
+
+#[cfg(feature = "std")]
 impl Default for Interpreter {
     fn default() -> Self {
-        let mut memory = [0; 4096];
-        memory[0..FONT.len()].copy_from_slice(&FONT);
-
-        Self {
-            pc: 0x200,
-            index: 0x200,
-            memory,
-            sp: Default::default(),
-            delay_timer: Default::default(),
-            sound_timer: Default::default(),
-            v: Default::default(),
-            stack: Default::default(),
-            screen_buffer: [0; 64 * 32],
-        }
+        Self::with_random(Xorshift::default())
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use super::Interpreter;
+    use super::{ExecError, Interpreter, InterpreterEvent, BIG_FONT, MAX_MEMORY};
+    use crate::quirks::Quirks;
+    use crate::random::{Scripted, Xorshift};
+    use alloc::vec;
 
     #[test]
     fn test_1nnn() {
         let mut interpreter = Interpreter::default();
 
-        interpreter.load(&[0x12, 0x34]);
-        interpreter.cycle();
+        interpreter.load(&[0x12, 0x34]).unwrap();
+        interpreter.cycle().unwrap();
 
         assert_eq!(interpreter.pc, 0x234);
     }
@@ -391,8 +950,8 @@ mod tests {
     fn test_2nnn() {
         let mut interpeter = Interpreter::default();
 
-        interpeter.load(&[0x23, 0x45]);
-        interpeter.cycle();
+        interpeter.load(&[0x23, 0x45]).unwrap();
+        interpeter.cycle().unwrap();
 
         assert_eq!(interpeter.sp, 1);
         assert_eq!(interpeter.pc, 0x345);
@@ -403,14 +962,14 @@ mod tests {
     fn test_3xnn() {
         let mut interpreter = Interpreter::default();
 
-        interpreter.load(&[0x32, 0xAB]);
+        interpreter.load(&[0x32, 0xAB]).unwrap();
         interpreter.v[2] = 0xAB;
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.pc, 0x200 + 4);
 
-        interpreter.load(&[0x32, 0xAB]);
+        interpreter.load(&[0x32, 0xAB]).unwrap();
         interpreter.v[2] = 0xAB + 1;
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.pc, 0x200 + 2);
     }
 
@@ -419,15 +978,15 @@ mod tests {
         let mut interpreter = Interpreter::default();
 
         // Test case 1: When Vx == NN, the program counter should not skip (increase by 2)
-        interpreter.load(&[0x32, 0x12]); // Set V1 to 0x12
+        interpreter.load(&[0x42, 0x12]).unwrap();
         interpreter.v[2] = 0x12;
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.pc, 0x200 + 2);
 
         // Test case 2: When Vx != NN, the program counter should skip (increase by 4)
-        interpreter.load(&[0x32, 0x34]); // Set V1 to 0x34
+        interpreter.load(&[0x42, 0x34]).unwrap();
         interpreter.v[2] = 0x12;
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.pc, 0x200 + 4);
     }
 
@@ -435,18 +994,18 @@ mod tests {
     fn test_5xy0() {
         // Test the 5XY0 opcode when registers Vx and Vy are equal. (increase by 4)
         let mut interpreter = Interpreter::default();
-        interpreter.load(&[0x52, 0x30]);
+        interpreter.load(&[0x52, 0x30]).unwrap();
         interpreter.v[2] = 0x30;
         interpreter.v[3] = 0x30;
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.pc, 0x200 + 4);
 
         // Test the 5XY0 opcode when registers Vx and Vy are not equal. (increase by 2)
         let mut interpreter = Interpreter::default();
-        interpreter.load(&[0x54, 0x50]);
+        interpreter.load(&[0x54, 0x50]).unwrap();
         interpreter.v[4] = 0x50;
         interpreter.v[5] = 0x20;
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.pc, 0x200 + 2);
     }
 
@@ -454,8 +1013,8 @@ mod tests {
     fn test_6xnn() {
         // Test the 6XNN opcode (MOV VX, NN) to load a value into a register.
         let mut interpreter = Interpreter::default();
-        interpreter.load(&[0x61, 0x42]);
-        interpreter.cycle();
+        interpreter.load(&[0x61, 0x42]).unwrap();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.v[1], 0x42);
         assert_eq!(interpreter.pc, 0x200 + 2);
     }
@@ -464,11 +1023,11 @@ mod tests {
     fn test_7xnn() {
         // Test the 7XNN opcode (ADD VX, NN) to add a value to a register.
         let mut interpreter = Interpreter::default();
-        interpreter.load(&[0x61, 0x42, 0x71, 0x0A]);
-        interpreter.cycle();
+        interpreter.load(&[0x61, 0x42, 0x71, 0x0A]).unwrap();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.v[1], 0x42);
 
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
         assert_eq!(interpreter.v[1], 0x4C);
         assert_eq!(interpreter.pc, 0x200 + 4);
     }
@@ -477,13 +1036,24 @@ mod tests {
     fn test_sound_delay_and_play_sound() {
         let mut interpreter = Interpreter::default();
 
-        interpreter.load(&[0x61, 0x03, 0xF1, 0x18]);
-        interpreter.cycle();
-        interpreter.cycle();
+        interpreter
+            .load(&[0x61, 0x03, 0xF1, 0x18, 0x12, 0x04])
+            .unwrap();
+        interpreter.cycle().unwrap();
+        assert!(matches!(
+            interpreter.cycle().unwrap(),
+            InterpreterEvent::Audio
+        ));
+
+        // Executing instructions alone must not touch the timers.
+        for _ in 0..10 {
+            interpreter.cycle().unwrap();
+        }
+        assert_eq!(interpreter.sound_timer, 3);
 
-        // Execute the cycle for three iterations, which should decrement the sound_timer.
+        // Tick the timers for three frames, which should decrement the sound_timer.
         for _ in 0..3 {
-            interpreter.cycle();
+            interpreter.tick_timers();
         }
 
         // At this point, sound_timer should be 0.
@@ -494,6 +1064,9 @@ mod tests {
     fn test_fx55_reg_dump() {
         let mut interpreter = Interpreter::default();
 
+        // Load a program that uses REG_DUMP (FX55) to store the values of V0, V1, V2, and V3 in memory starting from the index register.
+        interpreter.load(&[0xF3, 0x55]).unwrap();
+
         // Initialize some values in registers
         interpreter.v[0] = 0x01;
         interpreter.v[1] = 0x02;
@@ -502,11 +1075,8 @@ mod tests {
 
         interpreter.index = 0x300;
 
-        // Load a program that uses REG_DUMP (FX55) to store the values of V0, V1, V2, and V3 in memory starting from the index register.
-        interpreter.load(&[0xF3, 0x55]);
-
         // Execute the program
-        interpreter.cycle();
+        interpreter.cycle().unwrap();
 
         // Check if the values were stored in memory as expected
         assert_eq!(interpreter.memory[0x300], 0x01);
@@ -514,7 +1084,703 @@ mod tests {
         assert_eq!(interpreter.memory[0x300 + 2], 0x03);
         assert_eq!(interpreter.memory[0x300 + 3], 0x04);
 
+        // By default the index register is left alone.
+        assert_eq!(interpreter.index, 0x300);
+
         // Make sure the program counter is incremented by 2 after the REG_DUMP instruction.
         assert_eq!(interpreter.pc, 0x200 + 2);
     }
+
+    #[test]
+    fn test_ex9e() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0xE1, 0x9E]).unwrap();
+        interpreter.v[1] = 0xA;
+        interpreter.press_key(0xA);
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x200 + 4);
+
+        interpreter.load(&[0xE1, 0x9E]).unwrap();
+        interpreter.v[1] = 0xA;
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x200 + 2);
+    }
+
+    #[test]
+    fn test_exa1() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0xE1, 0xA1]).unwrap();
+        interpreter.v[1] = 0x3;
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x200 + 4);
+
+        interpreter.load(&[0xE1, 0xA1]).unwrap();
+        interpreter.v[1] = 0x3;
+        interpreter.press_key(0x3);
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x200 + 2);
+    }
+
+    #[test]
+    fn test_fx0a_waits_for_press_and_release() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0xF5, 0x0A]).unwrap();
+
+        // Without any key the instruction keeps blocking.
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert!(interpreter.is_waiting_for_key());
+        assert_eq!(interpreter.pc, 0x200);
+
+        // Pressing alone is not enough, the key has to be released as well.
+        interpreter.press_key(0x7);
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x200);
+
+        interpreter.release_key(0x7);
+        interpreter.cycle().unwrap();
+        assert!(!interpreter.is_waiting_for_key());
+        assert_eq!(interpreter.v[5], 0x7);
+        assert_eq!(interpreter.pc, 0x200 + 2);
+    }
+
+    #[test]
+    fn test_fx0a_ignores_keys_held_before_waiting() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0xF5, 0x0A]).unwrap();
+        interpreter.press_key(0x1);
+        interpreter.cycle().unwrap();
+        interpreter.release_key(0x1);
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x200);
+    }
+
+    #[test]
+    fn test_fx0a_keeps_timers_running() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0xF0, 0x0A]).unwrap();
+        interpreter.sound_timer = 2;
+        interpreter.delay_timer = 2;
+        interpreter.run_frame().unwrap();
+        interpreter.run_frame().unwrap();
+        assert_eq!(interpreter.sound_timer, 0);
+        assert_eq!(interpreter.delay_timer, 0);
+        assert_eq!(interpreter.pc, 0x200);
+    }
+
+    #[test]
+    fn test_run_frame_executes_instructions_per_frame() {
+        let mut interpreter = Interpreter::default().with_instructions_per_frame(3);
+
+        // ADD V0, 1 repeated, so V0 counts the executed instructions.
+        interpreter.load(&[0x70, 0x01].repeat(8)).unwrap();
+        assert_eq!(interpreter.instructions_per_frame(), 3);
+
+        interpreter.run_frame().unwrap();
+        assert_eq!(interpreter.v[0], 3);
+
+        interpreter.run_frame().unwrap();
+        assert_eq!(interpreter.v[0], 6);
+    }
+
+    #[test]
+    fn test_delay_loop_terminates() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter
+            .load(&[
+                0x60, 0x05, // MOV V0, 5
+                0xF0, 0x15, // SET_DELAY V0
+                0xF1, 0x07, // MOVDELAY V1
+                0x31, 0x00, // JEQ V1, 0
+                0x12, 0x04, // JMP 0x204
+                0x12, 0x0A, // JMP 0x20A
+            ])
+            .unwrap();
+
+        let frames = (1..=10)
+            .find(|_| {
+                interpreter.run_frame().unwrap();
+                interpreter.pc == 0x20A
+            })
+            .expect("delay loop never finished");
+
+        assert_eq!(frames, 6);
+        assert_eq!(interpreter.delay_timer, 0);
+    }
+
+    #[test]
+    fn test_run_frame_reports_audio() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter
+            .load(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04])
+            .unwrap();
+
+        assert!(matches!(
+            interpreter.run_frame().unwrap(),
+            InterpreterEvent::Audio
+        ));
+        assert!(matches!(
+            interpreter.run_frame().unwrap(),
+            InterpreterEvent::Opcode(0x1204)
+        ));
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0x00, 0xE0, 0xFF, 0xFF]).unwrap();
+        interpreter.cycle().unwrap();
+
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::UnknownOpcode {
+                address: 0x202,
+                opcode: 0xFFFF
+            }
+        );
+        assert_eq!(interpreter.pc, 0x202);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut interpreter = Interpreter::default();
+
+        // CALL 0x200 recursively until the stack runs out.
+        interpreter.load(&[0x22, 0x00]).unwrap();
+
+        for _ in 0..16 {
+            interpreter.cycle().unwrap();
+        }
+
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::StackOverflow(0x200)
+        );
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0x00, 0xEE]).unwrap();
+
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::StackUnderflow(0x200)
+        );
+        assert_eq!(interpreter.sp, 0);
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut interpreter = Interpreter::default();
+
+        // LOAD I 0xFFE, DRAW V0, V0, 3
+        interpreter.load(&[0xAF, 0xFE, 0xD0, 0x03]).unwrap();
+        interpreter.cycle().unwrap();
+
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::MemoryOutOfBounds {
+                address: 0x202,
+                index: 0xFFE,
+                len: 3
+            }
+        );
+
+        // STORE_BCD V0
+        interpreter.load(&[0xAF, 0xFE, 0xF0, 0x33]).unwrap();
+        interpreter.cycle().unwrap();
+        assert!(matches!(
+            interpreter.cycle(),
+            Err(ExecError::MemoryOutOfBounds { .. })
+        ));
+
+        // REG_LOAD [I] V2
+        interpreter.load(&[0xAF, 0xFE, 0xF2, 0x65]).unwrap();
+        interpreter.cycle().unwrap();
+        assert!(matches!(
+            interpreter.cycle(),
+            Err(ExecError::MemoryOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pc_out_of_range() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0x1F, 0xFF]).unwrap();
+        interpreter.cycle().unwrap();
+
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::PcOutOfRange(0xFFF)
+        );
+    }
+
+    #[test]
+    fn test_program_too_large() {
+        let mut interpreter = Interpreter::default();
+
+        assert_eq!(
+            interpreter.load(&[0; 4096]).unwrap_err(),
+            ExecError::ProgramTooLarge(4096)
+        );
+    }
+
+    #[test]
+    fn test_quirk_shift() {
+        // SHR V1, V2 | SHL V3, V4
+        let program = [0x81, 0x26, 0x83, 0x4E];
+
+        let mut interpreter = Interpreter::from(Quirks::COSMAC_VIP);
+        interpreter.load(&program).unwrap();
+        interpreter.v[1] = 0xFF;
+        interpreter.v[2] = 0x05;
+        interpreter.v[3] = 0xFF;
+        interpreter.v[4] = 0x41;
+        interpreter.cycle().unwrap();
+        assert_eq!((interpreter.v[1], interpreter.v[0xF]), (0x02, 1));
+        interpreter.cycle().unwrap();
+        assert_eq!((interpreter.v[3], interpreter.v[0xF]), (0x82, 0));
+
+        let mut interpreter = Interpreter::from(Quirks::SCHIP_1_1);
+        interpreter.load(&program).unwrap();
+        interpreter.v[1] = 0xFF;
+        interpreter.v[2] = 0x05;
+        interpreter.v[3] = 0xFF;
+        interpreter.v[4] = 0x41;
+        interpreter.cycle().unwrap();
+        assert_eq!((interpreter.v[1], interpreter.v[0xF]), (0x7F, 1));
+        interpreter.cycle().unwrap();
+        assert_eq!((interpreter.v[3], interpreter.v[0xF]), (0xFE, 1));
+    }
+
+    #[test]
+    fn test_quirk_load_store_increment() {
+        let program = [0xA3, 0x00, 0xF2, 0x55];
+
+        let mut interpreter = Interpreter::from(Quirks::COSMAC_VIP);
+        interpreter.load(&program).unwrap();
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.index, 0x303);
+
+        let mut interpreter = Interpreter::from(Quirks::SCHIP_1_1);
+        interpreter.load(&program).unwrap();
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.index, 0x300);
+    }
+
+    #[test]
+    fn test_quirk_jump_with_vx() {
+        let program = [0xB3, 0x00];
+
+        let mut interpreter = Interpreter::from(Quirks::COSMAC_VIP);
+        interpreter.load(&program).unwrap();
+        interpreter.v[0] = 0x10;
+        interpreter.v[3] = 0x20;
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x310);
+
+        let mut interpreter = Interpreter::from(Quirks::CHIP_48);
+        interpreter.load(&program).unwrap();
+        interpreter.v[0] = 0x10;
+        interpreter.v[3] = 0x20;
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x320);
+    }
+
+    #[test]
+    fn test_quirk_vf_reset() {
+        let program = [0x81, 0x21];
+
+        let mut interpreter = Interpreter::from(Quirks::COSMAC_VIP);
+        interpreter.load(&program).unwrap();
+        interpreter.v[0xF] = 1;
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.v[0xF], 0);
+
+        let mut interpreter = Interpreter::from(Quirks::XO_CHIP);
+        interpreter.load(&program).unwrap();
+        interpreter.v[0xF] = 1;
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.v[0xF], 1);
+    }
+
+    #[test]
+    fn test_quirk_clipping() {
+        // Draw the "0" glyph at (62, 30), its right and bottom halves fall off the screen.
+        let program = [0x60, 0x3E, 0x61, 0x1E, 0xA0, 0x00, 0xD0, 0x15];
+
+        let mut interpreter = Interpreter::from(Quirks::COSMAC_VIP);
+        interpreter.load(&program).unwrap();
+        for _ in 0..4 {
+            interpreter.cycle().unwrap();
+        }
+        assert_eq!(interpreter.screen.pixels()[30 * 64 + 62], 1);
+        assert_eq!(interpreter.screen.pixels()[30 * 64], 0);
+        assert_eq!(interpreter.screen.pixels()[1], 0);
+
+        let mut interpreter = Interpreter::from(Quirks::XO_CHIP);
+        interpreter.load(&program).unwrap();
+        for _ in 0..4 {
+            interpreter.cycle().unwrap();
+        }
+        assert_eq!(interpreter.screen.pixels()[30 * 64 + 62], 1);
+        assert_eq!(interpreter.screen.pixels()[30 * 64], 1);
+        assert_eq!(interpreter.screen.pixels()[1], 1);
+    }
+
+    #[test]
+    fn test_quirk_display_wait() {
+        // Two draws in a row, the second one has to wait for the next frame.
+        let program = [0xD0, 0x01, 0xD0, 0x01];
+
+        let mut interpreter = Interpreter::from(Quirks::COSMAC_VIP);
+        interpreter.load(&program).unwrap();
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x202);
+        interpreter.tick_timers();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x204);
+
+        let mut interpreter = Interpreter::from(Quirks::SCHIP_1_1);
+        interpreter.load(&program).unwrap();
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.pc, 0x204);
+    }
+
+    #[test]
+    fn test_8xy5_and_8xy7_set_vf_when_not_borrowing() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0x81, 0x25, 0x83, 0x47]).unwrap();
+        interpreter.v[1] = 5;
+        interpreter.v[2] = 3;
+        interpreter.v[3] = 5;
+        interpreter.v[4] = 3;
+        interpreter.cycle().unwrap();
+        assert_eq!((interpreter.v[1], interpreter.v[0xF]), (2, 1));
+        interpreter.cycle().unwrap();
+        assert_eq!((interpreter.v[3], interpreter.v[0xF]), (254, 0));
+    }
+
+    #[test]
+    fn test_quirks_survive_load() {
+        let mut interpreter = Interpreter::from(Quirks::XO_CHIP);
+
+        interpreter.load(&[0x00, 0xE0]).unwrap();
+        assert_eq!(interpreter.quirks(), Quirks::XO_CHIP);
+    }
+
+    #[test]
+    fn test_00fe_00ff_resolution() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0x00, 0xFF, 0x00, 0xFE]).unwrap();
+
+        interpreter.cycle().unwrap();
+        assert!(interpreter.screen().is_hires());
+        assert_eq!(interpreter.screen().width(), 128);
+        assert_eq!(interpreter.screen().height(), 64);
+
+        interpreter.cycle().unwrap();
+        assert!(!interpreter.screen().is_hires());
+        assert_eq!(interpreter.screen().width(), 64);
+        assert_eq!(interpreter.screen().height(), 32);
+    }
+
+    #[test]
+    fn test_00cn_00fb_00fc_scroll() {
+        let mut interpreter = Interpreter::from(Quirks::SCHIP_1_1);
+
+        // HIGH, LOAD I 0, DRAW V0, V0, 1, SCD 3, SCR, SCL
+        interpreter
+            .load(&[
+                0x00, 0xFF, 0xA0, 0x00, 0xD0, 0x01, 0x00, 0xC3, 0x00, 0xFB, 0x00, 0xFC,
+            ])
+            .unwrap();
+
+        for _ in 0..4 {
+            interpreter.cycle().unwrap();
+        }
+        assert_eq!(interpreter.screen().pixel(0, 0), 0);
+        assert_eq!(interpreter.screen().pixel(0, 3), 1);
+        assert_eq!(interpreter.screen().pixel(3, 3), 1);
+
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.screen().pixel(0, 3), 0);
+        assert_eq!(interpreter.screen().pixel(4, 3), 1);
+        assert_eq!(interpreter.screen().pixel(7, 3), 1);
+
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.screen().pixel(0, 3), 1);
+        assert_eq!(interpreter.screen().pixel(4, 3), 0);
+    }
+
+    #[test]
+    fn test_00fd_exit() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0x00, 0xFD]).unwrap();
+
+        assert_eq!(interpreter.cycle().unwrap(), InterpreterEvent::Exit);
+        assert_eq!(interpreter.run_frame().unwrap(), InterpreterEvent::Exit);
+        assert!(interpreter.is_halted());
+        assert_eq!(interpreter.pc, 0x200);
+    }
+
+    #[test]
+    fn test_dxy0_draws_16x16_sprite() {
+        let mut interpreter = Interpreter::from(Quirks::SCHIP_1_1);
+
+        let mut program = vec![0x00, 0xFF, 0xA2, 0x08, 0xD0, 0x00, 0x12, 0x06];
+        program.extend([0xFF; 32]);
+
+        interpreter.load(&program).unwrap();
+        for _ in 0..3 {
+            interpreter.cycle().unwrap();
+        }
+
+        let lit = interpreter
+            .screen()
+            .pixels()
+            .iter()
+            .filter(|&&pixel| pixel != 0);
+        assert_eq!(lit.count(), 16 * 16);
+        assert_eq!(interpreter.screen().pixel(15, 15), 1);
+        assert_eq!(interpreter.screen().pixel(16, 0), 0);
+        assert_eq!(interpreter.v[0xF], 0);
+    }
+
+    #[test]
+    fn test_fx30_big_font() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter.load(&[0xF1, 0x30]).unwrap();
+        interpreter.v[1] = 0x2;
+        interpreter.cycle().unwrap();
+
+        let index = interpreter.index as usize;
+        assert_eq!(&interpreter.memory[index..index + 10], &BIG_FONT[20..30]);
+    }
+
+    #[test]
+    fn test_fx75_fx85_flags() {
+        let mut interpreter = Interpreter::default();
+
+        interpreter
+            .load(&[0xF3, 0x75, 0x60, 0x00, 0xF3, 0x85])
+            .unwrap();
+        interpreter.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.v[0], 0);
+
+        interpreter.cycle().unwrap();
+        assert_eq!(&interpreter.v[..4], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_f000_long_index() {
+        let mut interpreter = Interpreter::from(Quirks::XO_CHIP).with_memory_size(MAX_MEMORY);
+
+        interpreter.load(&[0xF0, 0x00, 0xAB, 0xCD]).unwrap();
+        interpreter.cycle().unwrap();
+
+        assert_eq!(interpreter.index, 0xABCD);
+        assert_eq!(interpreter.pc, 0x204);
+    }
+
+    #[test]
+    fn test_skip_over_f000() {
+        let mut interpreter = Interpreter::default();
+
+        // JEQ V0, 0 followed by LOAD_LONG I, 0x1234
+        interpreter
+            .load(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34])
+            .unwrap();
+        interpreter.cycle().unwrap();
+
+        assert_eq!(interpreter.pc, 0x206);
+    }
+
+    #[test]
+    fn test_5xy2_5xy3_register_ranges() {
+        let mut interpreter = Interpreter::from(Quirks::XO_CHIP);
+
+        // LOAD I 0x300, SAVE V1, V3, SAVE V3, V1 at 0x310, LOAD V4, V6
+        interpreter
+            .load(&[0xA3, 0x00, 0x51, 0x32, 0xA3, 0x10, 0x53, 0x12, 0x54, 0x63])
+            .unwrap();
+        interpreter.v[1..4].copy_from_slice(&[1, 2, 3]);
+
+        for _ in 0..4 {
+            interpreter.cycle().unwrap();
+        }
+        assert_eq!(&interpreter.memory[0x300..0x303], &[1, 2, 3]);
+        assert_eq!(&interpreter.memory[0x310..0x313], &[3, 2, 1]);
+        assert_eq!(interpreter.index, 0x310);
+
+        interpreter.cycle().unwrap();
+        assert_eq!(&interpreter.v[4..7], &[3, 2, 1]);
+    }
+
+    #[test]
+    fn test_fn01_planes() {
+        let mut interpreter = Interpreter::from(Quirks::XO_CHIP);
+
+        // PLANE 3, LOAD I 0x20A, DRAW V0, V0, 1, JMP 0x208, sprites for both planes
+        interpreter
+            .load(&[
+                0xF3, 0x01, 0xA2, 0x0A, 0xD0, 0x01, 0x12, 0x06, 0x00, 0x00, 0x80, 0xC0,
+            ])
+            .unwrap();
+
+        for _ in 0..3 {
+            interpreter.cycle().unwrap();
+        }
+
+        assert_eq!(interpreter.screen().planes(), 3);
+        assert_eq!(interpreter.screen().pixel(0, 0), 0b11);
+        assert_eq!(interpreter.screen().pixel(1, 0), 0b10);
+    }
+
+    #[test]
+    fn test_f002_fx3a_audio() {
+        let mut interpreter = Interpreter::from(Quirks::XO_CHIP);
+
+        let mut program = vec![0xA2, 0x08, 0xF0, 0x02, 0xF1, 0x3A, 0x12, 0x06];
+        program.extend(0..16);
+
+        interpreter.load(&program).unwrap();
+        interpreter.v[1] = 0x70;
+
+        for _ in 0..3 {
+            interpreter.cycle().unwrap();
+        }
+
+        assert_eq!(interpreter.audio_pattern()[15], 15);
+        assert_eq!(interpreter.pitch(), 0x70);
+    }
+
+    #[test]
+    fn test_memory_size() {
+        // STORE_BCD above 4 KiB
+        let program = [0xF0, 0x00, 0x80, 0x00, 0xF0, 0x33];
+
+        let mut interpreter = Interpreter::default();
+        interpreter.load(&program).unwrap();
+        interpreter.cycle().unwrap();
+        assert!(interpreter.cycle().is_err());
+
+        let mut interpreter = Interpreter::default().with_memory_size(MAX_MEMORY);
+        interpreter.load(&program).unwrap();
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.memory_size(), MAX_MEMORY);
+    }
+
+    #[test]
+    fn test_end_of_memory() {
+        let mut interpreter = Interpreter::default().with_memory_size(MAX_MEMORY);
+
+        // LD V0, 0x12 at the last address
+        interpreter.pc = 0xFFFE;
+        interpreter.memory[0xFFFE..].copy_from_slice(&[0x60, 0x12]);
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::PcOutOfRange(0xFFFE)
+        );
+
+        // JEQ V0, 0x12 skipping past the end
+        interpreter.pc = 0xFFFC;
+        interpreter.memory[0xFFFC..0xFFFE].copy_from_slice(&[0x30, 0x12]);
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::PcOutOfRange(0xFFFC)
+        );
+
+        // LOAD_LONG I without its operand
+        interpreter.pc = 0xFFFE;
+        interpreter.memory[0xFFFE..].copy_from_slice(&[0xF0, 0x00]);
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::PcOutOfRange(0xFFFF)
+        );
+
+        // REG_DUMP [I] V0 into the last byte, then V1 past it
+        let quirks = Quirks {
+            load_store_increment: true,
+            ..Quirks::default()
+        };
+        let mut interpreter = Interpreter::from(quirks).with_memory_size(MAX_MEMORY);
+
+        interpreter.load(&[0xF0, 0x55, 0xF1, 0x55]).unwrap();
+        interpreter.v[0] = 0xAB;
+        interpreter.index = 0xFFFF;
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.memory[0xFFFF], 0xAB);
+        assert_eq!(interpreter.index, 0);
+
+        interpreter.index = 0xFFFF;
+        assert_eq!(
+            interpreter.cycle().unwrap_err(),
+            ExecError::MemoryOutOfBounds {
+                address: 0x202,
+                index: 0xFFFF,
+                len: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_cxnn_scripted() {
+        let mut interpreter = Interpreter::with_random(Scripted::new([0xFF, 0x5A]));
+
+        interpreter.load(&[0xC0, 0x0F, 0xC1, 0xFF]).unwrap();
+        interpreter.cycle().unwrap();
+        interpreter.cycle().unwrap();
+        assert_eq!(interpreter.v[0], 0x0F);
+        assert_eq!(interpreter.v[1], 0x5A);
+    }
+
+    #[test]
+    fn test_cxnn_fixed_seed_survives_load() {
+        let program = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF];
+
+        let run = |interpreter: &mut Interpreter| {
+            interpreter.load(&program).unwrap();
+
+            for _ in 0..3 {
+                interpreter.cycle().unwrap();
+            }
+
+            interpreter.v
+        };
+
+        let mut a = Interpreter::with_random(Xorshift::new(1234));
+        let mut b = Interpreter::with_random(Xorshift::new(1234));
+
+        let first = run(&mut a);
+        assert_eq!(first, run(&mut b));
+
+        // Loading doesn't reseed, the sequence continues.
+        let second = run(&mut a);
+        assert_ne!(first, second);
+        assert_eq!(second, run(&mut b));
+    }
 }
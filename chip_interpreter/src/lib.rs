@@ -1 +1,20 @@
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+pub mod debugger;
+pub mod framebuffer;
+#[cfg(feature = "std")]
+pub mod gdb;
+pub mod host;
 pub mod interpreter;
+pub mod quirks;
+pub mod random;
+#[cfg(feature = "alloc")]
+pub mod rewind;
+pub mod snapshot;
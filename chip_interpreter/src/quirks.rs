@@ -0,0 +1,68 @@
+// Behaviour of the instructions that differ between the CHIP-8 platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VX in place instead of shifting VY into VX.
+    pub shift: bool,
+    // FX55/FX65 increment I by X + 1.
+    pub load_store_increment: bool,
+    // BXNN jumps to XNN + VX instead of BNNN jumping to NNN + V0.
+    pub jump_with_vx: bool,
+    // 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    // DXYN clips sprites at the edges of the screen instead of wrapping them.
+    pub clipping: bool,
+    // DXYN waits for the vertical blank, so only one sprite is drawn per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Self = Self {
+        shift: false,
+        load_store_increment: true,
+        jump_with_vx: false,
+        vf_reset: true,
+        clipping: true,
+        display_wait: true,
+    };
+
+    pub const CHIP_48: Self = Self {
+        shift: true,
+        load_store_increment: true,
+        jump_with_vx: true,
+        vf_reset: false,
+        clipping: true,
+        display_wait: false,
+    };
+
+    pub const SCHIP_1_1: Self = Self {
+        shift: true,
+        load_store_increment: false,
+        jump_with_vx: true,
+        vf_reset: false,
+        clipping: true,
+        display_wait: false,
+    };
+
+    pub const XO_CHIP: Self = Self {
+        shift: false,
+        load_store_increment: true,
+        jump_with_vx: false,
+        vf_reset: false,
+        clipping: false,
+        display_wait: false,
+    };
+}
+
+// The behaviour from before quirks were configurable, pick a preset to match a platform.
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: true,
+            load_store_increment: false,
+            jump_with_vx: false,
+            vf_reset: false,
+            clipping: false,
+            display_wait: false,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// Source of the random bytes used by `RAND VX, NN | CXNN`.
+pub trait RandomSource {
+    fn next_u8(&mut self) -> u8;
+}
+
+// xorshift64* generator, small and fast enough to be seeded for reproducible runs.
+#[derive(Debug, Clone)]
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Self {
+        // An all zero state would only ever produce zeroes.
+        let state = if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        };
+
+        Self { state }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Xorshift {
+    // Seeded from the operating system, use `Xorshift::new` for a deterministic sequence.
+    fn default() -> Self {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+
+        // Every `RandomState` gets fresh keys from the operating system's random source.
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u8(0);
+
+        Self::new(hasher.finish())
+    }
+}
+
+impl RandomSource for Xorshift {
+    fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
+
+// Plays back a fixed list of values, starting over once it is exhausted.
+#[derive(Debug, Clone)]
+pub struct Scripted<T> {
+    values: T,
+    position: usize,
+}
+
+impl<T: AsRef<[u8]>> Scripted<T> {
+    pub fn new(values: T) -> Self {
+        Self {
+            values,
+            position: 0,
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> RandomSource for Scripted<T> {
+    fn next_u8(&mut self) -> u8 {
+        let values = self.values.as_ref();
+
+        if values.is_empty() {
+            return 0;
+        }
+
+        let value = values[self.position % values.len()];
+        self.position = (self.position + 1) % values.len();
+
+        value
+    }
+}
+
+impl<F: FnMut() -> u8> RandomSource for F {
+    fn next_u8(&mut self) -> u8 {
+        self()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{RandomSource, Scripted, Xorshift};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_xorshift_seed() {
+        let mut a = Xorshift::new(42);
+        let mut b = Xorshift::new(42);
+        let mut c = Xorshift::new(43);
+
+        let a: Vec<u8> = (0..32).map(|_| a.next_u8()).collect();
+        let b: Vec<u8> = (0..32).map(|_| b.next_u8()).collect();
+        let c: Vec<u8> = (0..32).map(|_| c.next_u8()).collect();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_xorshift_zero_seed() {
+        let mut random = Xorshift::new(0);
+
+        assert!((0..32).any(|_| random.next_u8() != 0));
+    }
+
+    #[test]
+    fn test_scripted() {
+        let mut random = Scripted::new([1, 2, 3]);
+
+        let values: Vec<u8> = (0..7).map(|_| random.next_u8()).collect();
+        assert_eq!(values, [1, 2, 3, 1, 2, 3, 1]);
+
+        assert_eq!(Scripted::new([]).next_u8(), 0);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::interpreter::{Access, ExecError, Interpreter, InterpreterEvent, KeyWait};
+use crate::random::{RandomSource, Xorshift};
+use crate::snapshot::Snapshot;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+// Keeps a history of the last frames executed by the wrapped interpreter so it can be stepped
+// backwards, either instruction by instruction or a whole frame at a time.
+//
+// Every executed instruction (and every timer tick) records the registers it started from and
+// the bytes of memory and screen it changed, XORed with their previous value, which makes each
+// step cheap to store and to undo.
+pub struct Rewind<R = Xorshift> {
+    pub(crate) interpreter: Interpreter<R>,
+    frames: VecDeque<Vec<Step>>,
+    capacity: usize,
+    memory: Vec<u8>,
+    pixels: Vec<u8>,
+}
+
+struct Step {
+    registers: Registers,
+    memory: Delta,
+    pixels: Delta,
+    tick: bool,
+}
+
+// Everything besides memory and the screen pixels, host owned configuration isn't rewound.
+#[derive(Clone, Copy)]
+struct Registers {
+    pc: u16,
+    index: u16,
+    sp: u8,
+    delay_timer: u8,
+    sound_timer: u8,
+    v: [u8; 16],
+    stack: [u16; 16],
+    key_wait: KeyWait,
+    vblank_wait: bool,
+    halted: bool,
+    rpl: [u8; 16],
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    hires: bool,
+    planes: u8,
+}
+
+impl Registers {
+    fn capture<R>(interpreter: &Interpreter<R>) -> Self {
+        Self {
+            pc: interpreter.pc,
+            index: interpreter.index,
+            sp: interpreter.sp,
+            delay_timer: interpreter.delay_timer,
+            sound_timer: interpreter.sound_timer,
+            v: interpreter.v,
+            stack: interpreter.stack,
+            key_wait: interpreter.key_wait,
+            vblank_wait: interpreter.vblank_wait,
+            halted: interpreter.halted,
+            rpl: interpreter.rpl,
+            audio_pattern: interpreter.audio_pattern,
+            pitch: interpreter.pitch,
+            hires: interpreter.screen.hires,
+            planes: interpreter.screen.planes,
+        }
+    }
+
+    fn apply<R>(self, interpreter: &mut Interpreter<R>) {
+        interpreter.pc = self.pc;
+        interpreter.index = self.index;
+        interpreter.sp = self.sp;
+        interpreter.delay_timer = self.delay_timer;
+        interpreter.sound_timer = self.sound_timer;
+        interpreter.v = self.v;
+        interpreter.stack = self.stack;
+        interpreter.key_wait = self.key_wait;
+        interpreter.vblank_wait = self.vblank_wait;
+        interpreter.halted = self.halted;
+        interpreter.rpl = self.rpl;
+        interpreter.audio_pattern = self.audio_pattern;
+        interpreter.pitch = self.pitch;
+        interpreter.screen.hires = self.hires;
+        interpreter.screen.planes = self.planes;
+    }
+}
+
+// Runs of changed bytes stored as `(offset, len)` with the XOR of old and new values in `bytes`.
+#[derive(Default)]
+struct Delta {
+    runs: Vec<(u32, u32)>,
+    bytes: Vec<u8>,
+}
+
+impl Delta {
+    // Records the differences between `shadow` and `current`, which start at offset `start`, and
+    // brings `shadow` up to date.
+    fn diff(shadow: &mut [u8], current: &[u8], start: usize) -> Self {
+        const CHUNK: usize = 64;
+
+        let mut delta = Self::default();
+
+        for (chunk, (old, new)) in shadow
+            .chunks_mut(CHUNK)
+            .zip(current.chunks(CHUNK))
+            .enumerate()
+        {
+            if old == new {
+                continue;
+            }
+
+            for (i, (old, &new)) in old.iter_mut().zip(new).enumerate() {
+                if *old == new {
+                    continue;
+                }
+
+                let offset = (start + chunk * CHUNK + i) as u32;
+
+                match delta.runs.last_mut() {
+                    Some((start, len)) if *start + *len == offset => *len += 1,
+                    _ => delta.runs.push((offset, 1)),
+                }
+
+                delta.bytes.push(*old ^ new);
+                *old = new;
+            }
+        }
+
+        delta
+    }
+
+    fn undo(&self, shadow: &mut [u8], current: &mut [u8]) {
+        let mut bytes = self.bytes.iter();
+
+        for &(offset, len) in &self.runs {
+            let range = offset as usize..(offset + len) as usize;
+
+            for (byte, xor) in current[range].iter_mut().zip(bytes.by_ref()) {
+                *byte ^= xor;
+            }
+        }
+
+        for &(offset, len) in &self.runs {
+            let range = offset as usize..(offset + len) as usize;
+            shadow[range.clone()].copy_from_slice(&current[range]);
+        }
+    }
+}
+
+impl<R: RandomSource> Rewind<R> {
+    // Keeps up to `capacity` frames of history, one second is 60 frames.
+    pub fn new(interpreter: Interpreter<R>, capacity: usize) -> Self {
+        Self {
+            memory: interpreter.memory.to_vec(),
+            pixels: interpreter.screen.pixels.to_vec(),
+            interpreter,
+            frames: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn into_inner(self) -> Interpreter<R> {
+        self.interpreter
+    }
+
+    pub fn load(&mut self, program: &[u8]) -> Result<(), ExecError> {
+        self.interpreter.load(program)?;
+        self.clear();
+
+        Ok(())
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.interpreter.restore(snapshot);
+        self.clear();
+    }
+
+    pub fn press_key(&mut self, key: u8) {
+        self.interpreter.press_key(key);
+    }
+
+    pub fn release_key(&mut self, key: u8) {
+        self.interpreter.release_key(key);
+    }
+
+    // Drops the history, e.g. after the wrapped interpreter was changed from outside.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.memory.copy_from_slice(&self.interpreter.memory);
+        self.pixels.copy_from_slice(&self.interpreter.screen.pixels);
+    }
+
+    // Number of frames that can currently be stepped back.
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn cycle(&mut self) -> Result<InterpreterEvent, ExecError> {
+        let opcode = self.execute()?;
+
+        Ok(self.interpreter.event(opcode))
+    }
+
+    pub fn run_frame(&mut self) -> Result<InterpreterEvent, ExecError> {
+        let mut opcode = self.execute()?;
+
+        for _ in 1..self.interpreter.instructions_per_frame {
+            if self.interpreter.halted {
+                break;
+            }
+
+            opcode = self.execute()?;
+        }
+
+        self.record(true, |interpreter| {
+            interpreter.tick_timers();
+            Ok(())
+        })?;
+
+        Ok(self.interpreter.event(opcode))
+    }
+
+    // Undoes the last executed instruction, returns false once the history is exhausted.
+    pub fn step_back(&mut self) -> bool {
+        while let Some(frame) = self.frames.back_mut() {
+            let Some(step) = frame.pop() else {
+                self.frames.pop_back();
+                continue;
+            };
+
+            if frame.is_empty() {
+                self.frames.pop_back();
+            }
+
+            self.undo(&step);
+
+            if !step.tick {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Returns to the start of the last (possibly partially executed) frame.
+    pub fn step_back_frame(&mut self) -> bool {
+        let Some(frame) = self.frames.pop_back() else {
+            return false;
+        };
+
+        for step in frame.iter().rev() {
+            self.undo(step);
+        }
+
+        true
+    }
+
+    fn execute(&mut self) -> Result<u16, ExecError> {
+        self.record(false, Interpreter::execute)
+    }
+
+    fn record<T>(
+        &mut self,
+        tick: bool,
+        f: impl FnOnce(&mut Interpreter<R>) -> Result<T, ExecError>,
+    ) -> Result<T, ExecError> {
+        let registers = Registers::capture(&self.interpreter);
+        let pixels = self.interpreter.screen.pixels().len();
+        let result = f(&mut self.interpreter);
+
+        // Instructions only change memory through the range they report, the timer tick never.
+        let written = match self.interpreter.last_access {
+            Some((Access::Write, ref range)) if !tick => range.clone(),
+            _ => 0..0,
+        };
+
+        // Switching the resolution clears the whole screen, so diff the larger of both.
+        let pixels = pixels.max(self.interpreter.screen.pixels().len());
+
+        let step = Step {
+            registers,
+            memory: Delta::diff(
+                &mut self.memory[written.clone()],
+                &self.interpreter.memory[written.clone()],
+                written.start,
+            ),
+            pixels: Delta::diff(
+                &mut self.pixels[..pixels],
+                &self.interpreter.screen.pixels[..pixels],
+                0,
+            ),
+            tick,
+        };
+
+        // Instructions run with `cycle` alone still count as frames once there are enough.
+        let full = self.interpreter.instructions_per_frame as usize;
+
+        let closed = match self.frames.back() {
+            Some(frame) => frame
+                .last()
+                .map_or(true, |step| step.tick || (!tick && frame.len() >= full)),
+            None => true,
+        };
+
+        if closed {
+            self.frames.push_back(Vec::new());
+
+            if self.frames.len() > self.capacity {
+                self.frames.pop_front();
+            }
+        }
+
+        // Failed instructions are kept too, they may have changed the machine before failing.
+        self.frames.back_mut().unwrap().push(step);
+
+        result
+    }
+
+    fn undo(&mut self, step: &Step) {
+        step.registers.apply(&mut self.interpreter);
+        step.memory
+            .undo(&mut self.memory, &mut self.interpreter.memory);
+        step.pixels
+            .undo(&mut self.pixels, &mut self.interpreter.screen.pixels);
+        self.interpreter.screen.mark_all_dirty();
+    }
+}
+
+impl<R> Deref for Rewind<R> {
+    type Target = Interpreter<R>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.interpreter
+    }
+}
+
+impl<R: RandomSource> From<Interpreter<R>> for Rewind<R> {
+    // Ten seconds of history.
+    fn from(interpreter: Interpreter<R>) -> Self {
+        Self::new(interpreter, 600)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::Rewind;
+    use crate::interpreter::{ExecError, Interpreter, MAX_MEMORY};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    // Counts V0 up, stores it as BCD and draws the ones digit.
+    const PROGRAM: [u8; 18] = [
+        0xA3, 0x00, 0x70, 0x01, 0xF0, 0x33, 0xF2, 0x65, 0x00, 0xE0, 0xF2, 0x29, 0xD1, 0x15, 0x12,
+        0x02, 0x00, 0x00,
+    ];
+
+    fn rewind(capacity: usize) -> Rewind {
+        let mut interpreter = Interpreter::default();
+        interpreter.load(&PROGRAM).unwrap();
+
+        Rewind::new(interpreter, capacity)
+    }
+
+    fn state(rewind: &Rewind) -> Vec<u8> {
+        rewind.snapshot().to_bytes()
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut rewind = rewind(10);
+        let mut states = vec![state(&rewind)];
+
+        for _ in 0..20 {
+            rewind.cycle().unwrap();
+            states.push(state(&rewind));
+        }
+
+        states.pop();
+
+        while let Some(expected) = states.pop() {
+            assert!(rewind.step_back());
+            assert_eq!(state(&rewind), expected);
+        }
+
+        assert!(!rewind.step_back());
+    }
+
+    #[test]
+    fn test_step_back_frame() {
+        let mut rewind = rewind(10);
+        let mut states = vec![state(&rewind)];
+
+        for _ in 0..5 {
+            rewind.run_frame().unwrap();
+            states.push(state(&rewind));
+        }
+
+        states.pop();
+
+        while let Some(expected) = states.pop() {
+            assert!(rewind.step_back_frame());
+            assert_eq!(state(&rewind), expected);
+        }
+
+        assert!(!rewind.step_back_frame());
+
+        // The history is consistent again after rewinding.
+        rewind.run_frame().unwrap();
+        let after = state(&rewind);
+        rewind.run_frame().unwrap();
+        assert!(rewind.step_back_frame());
+        assert_eq!(state(&rewind), after);
+    }
+
+    #[test]
+    fn test_step_back_across_timer_tick() {
+        let mut rewind = rewind(10);
+
+        rewind.run_frame().unwrap();
+        let before = state(&rewind);
+        rewind.cycle().unwrap();
+
+        assert!(rewind.step_back());
+        assert_eq!(state(&rewind), before);
+
+        // Undoes the last instruction of the frame together with the timer tick.
+        assert!(rewind.step_back());
+        assert_eq!(rewind.pc, 0x206);
+    }
+
+    #[test]
+    fn test_step_back_failed_instruction() {
+        // REG_DUMP [I] V1 in the last two bytes stores the registers, then runs out of memory.
+        let mut interpreter = Interpreter::default().with_memory_size(MAX_MEMORY);
+        interpreter.memory[0xFFFE..].copy_from_slice(&[0xF1, 0x55]);
+        interpreter.pc = 0xFFFE;
+        interpreter.index = 0x300;
+        interpreter.v[..2].copy_from_slice(&[0xAB, 0xCD]);
+
+        let mut rewind = Rewind::new(interpreter, 10);
+        let before = state(&rewind);
+
+        assert_eq!(rewind.cycle(), Err(ExecError::PcOutOfRange(0xFFFE)));
+        assert_eq!(rewind.interpreter.memory[0x300..0x302], [0xAB, 0xCD]);
+
+        assert!(rewind.step_back());
+        assert_eq!(state(&rewind), before);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut rewind = rewind(3);
+
+        for _ in 0..10 {
+            rewind.run_frame().unwrap();
+        }
+
+        assert_eq!(rewind.frames(), 3);
+
+        for _ in 0..3 {
+            assert!(rewind.step_back_frame());
+        }
+
+        assert!(!rewind.step_back_frame());
+    }
+
+    #[test]
+    fn test_capacity_without_frames() {
+        let mut rewind = rewind(3);
+        let per_frame = rewind.instructions_per_frame() as usize;
+
+        for _ in 0..per_frame * 10 {
+            rewind.cycle().unwrap();
+        }
+
+        assert_eq!(rewind.frames(), 3);
+
+        for _ in 0..per_frame * 3 {
+            assert!(rewind.step_back());
+        }
+
+        assert!(!rewind.step_back());
+    }
+}
@@ -0,0 +1,325 @@
+use crate::framebuffer::{MAX_HEIGHT, MAX_WIDTH, PLANES};
+use crate::interpreter::{Interpreter, KeyWait, MAX_MEMORY};
+use crate::random::RandomSource;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+
+// Save state layout, all integers are little endian:
+//
+// | magic "CHIP8SAV" | version u16 | payload length u32 | payload | CRC-32 of payload u32 |
+const MAGIC: &[u8; 8] = b"CHIP8SAV";
+const VERSION: u16 = 1;
+#[cfg(feature = "alloc")]
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+
+// Machine state captured by `Interpreter::snapshot`.
+//
+// Configuration (quirks, instructions per frame), the keypad and the random source belong to the
+// host and are neither saved nor restored.
+#[derive(Clone)]
+pub struct Snapshot {
+    pub(crate) state: Interpreter<()>,
+}
+
+impl<R: RandomSource> Interpreter<R> {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut state = Interpreter::with_random(());
+        state.copy_state(self);
+
+        Snapshot { state }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.copy_state(&snapshot.state);
+    }
+}
+
+impl Snapshot {
+    #[cfg(feature = "alloc")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let state = &self.state;
+        let mut payload = Vec::with_capacity(state.memory_size + MAX_WIDTH * MAX_HEIGHT + 128);
+
+        payload.extend(state.pc.to_le_bytes());
+        payload.extend(state.index.to_le_bytes());
+        payload.extend([state.sp, state.delay_timer, state.sound_timer]);
+        payload.extend(state.v);
+        payload.extend(state.stack.iter().flat_map(|address| address.to_le_bytes()));
+        payload.extend((state.memory_size as u32).to_le_bytes());
+        payload.extend(&state.memory[..state.memory_size]);
+        payload.extend(match state.key_wait {
+            KeyWait::Idle => [0, 0],
+            KeyWait::Waiting => [1, 0],
+            KeyWait::Pressed(key) => [2, key],
+            KeyWait::Released(key) => [3, key],
+        });
+        payload.extend([state.vblank_wait as u8, state.halted as u8]);
+        payload.extend(state.rpl);
+        payload.extend(state.audio_pattern);
+        payload.push(state.pitch);
+        payload.extend([state.screen.hires as u8, state.screen.planes]);
+        payload.extend(state.screen.pixels);
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len() + 4);
+
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend((payload.len() as u32).to_le_bytes());
+        bytes.extend(&payload);
+        bytes.extend(crc32(&payload).to_le_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+
+        let version = reader.u16()?;
+
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let len = reader.u32()? as usize;
+        let payload = reader.take(len)?;
+        let checksum = reader.u32()?;
+
+        if crc32(payload) != checksum {
+            return Err(SnapshotError::ChecksumMismatch);
+        }
+
+        let mut reader = Reader(payload);
+        let mut state = Interpreter {
+            pc: reader.u16()?,
+            index: reader.u16()?,
+            sp: reader.u8()?,
+            delay_timer: reader.u8()?,
+            sound_timer: reader.u8()?,
+            ..Interpreter::with_random(())
+        };
+
+        state.v.copy_from_slice(reader.take(16)?);
+
+        for address in state.stack.iter_mut() {
+            *address = reader.u16()?;
+        }
+
+        state.memory_size = reader.u32()? as usize;
+
+        // Only the memory sizes `set_memory_size` allows are valid.
+        if !(0x1000..=MAX_MEMORY).contains(&state.memory_size)
+            || state.sp as usize > state.stack.len()
+        {
+            return Err(SnapshotError::Invalid);
+        }
+
+        state.memory[..state.memory_size].copy_from_slice(reader.take(state.memory_size)?);
+        state.key_wait = match (reader.u8()?, reader.u8()?) {
+            (0, _) => KeyWait::Idle,
+            (1, _) => KeyWait::Waiting,
+            (2, key) => KeyWait::Pressed(key & 0xF),
+            (3, key) => KeyWait::Released(key & 0xF),
+            _ => return Err(SnapshotError::Invalid),
+        };
+        state.vblank_wait = reader.u8()? != 0;
+        state.halted = reader.u8()? != 0;
+        state.rpl.copy_from_slice(reader.take(16)?);
+        state.audio_pattern.copy_from_slice(reader.take(16)?);
+        state.pitch = reader.u8()?;
+        state.screen.hires = reader.u8()? != 0;
+        state.screen.planes = reader.u8()?;
+
+        if state.screen.planes >> PLANES != 0 {
+            return Err(SnapshotError::Invalid);
+        }
+
+        state
+            .screen
+            .pixels
+            .copy_from_slice(reader.take(MAX_WIDTH * MAX_HEIGHT)?);
+
+        if !reader.0.is_empty() {
+            return Err(SnapshotError::Invalid);
+        }
+
+        Ok(Self { state })
+    }
+}
+
+struct Reader<'r>(&'r [u8]);
+
+impl<'r> Reader<'r> {
+    fn take(&mut self, len: usize) -> Result<&'r [u8], SnapshotError> {
+        if self.0.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+// CRC-32 (IEEE 802.3), bitwise since save states are small and rarely written.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    ChecksumMismatch,
+    Invalid,
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "Not a save state"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Save state version {} isn't supported", version)
+            }
+            Self::Truncated => write!(f, "Save state is truncated"),
+            Self::ChecksumMismatch => write!(f, "Save state checksum doesn't match"),
+            Self::Invalid => write!(f, "Save state contains invalid data"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{crc32, Snapshot, SnapshotError};
+    use crate::interpreter::Interpreter;
+    use crate::random::Xorshift;
+
+    // Counts V0 up and draws the counter digit, so memory, registers and screen all change.
+    const PROGRAM: [u8; 10] = [0x70, 0x01, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0x12, 0x00];
+
+    fn running() -> Interpreter {
+        let mut interpreter = Interpreter::with_random(Xorshift::new(1));
+
+        interpreter.load(&PROGRAM).unwrap();
+
+        for _ in 0..5 {
+            interpreter.run_frame().unwrap();
+        }
+
+        interpreter
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut interpreter = running();
+        let snapshot = interpreter.snapshot();
+        let v0 = interpreter.v[0];
+
+        interpreter.run_frame().unwrap();
+        assert_ne!(interpreter.v[0], v0);
+
+        interpreter.restore(&snapshot);
+        assert_eq!(interpreter.v[0], v0);
+        assert_eq!(interpreter.snapshot().to_bytes(), snapshot.to_bytes());
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let interpreter = running();
+        let bytes = interpreter.snapshot().to_bytes();
+
+        let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+        let mut restored = Interpreter::with_random(Xorshift::new(1));
+        restored.restore(&snapshot);
+
+        assert_eq!(restored.snapshot().to_bytes(), bytes);
+        assert_eq!(restored.screen().pixels(), interpreter.screen().pixels());
+
+        // Both continue identically.
+        let mut interpreter = interpreter;
+        interpreter.run_frame().unwrap();
+        restored.run_frame().unwrap();
+        assert_eq!(
+            restored.snapshot().to_bytes(),
+            interpreter.snapshot().to_bytes()
+        );
+    }
+
+    #[test]
+    fn test_corrupted_bytes() {
+        let bytes = running().snapshot().to_bytes();
+
+        let mut corrupted = bytes.clone();
+        corrupted[0x100] ^= 0xFF;
+        assert_eq!(
+            Snapshot::from_bytes(&corrupted).err(),
+            Some(SnapshotError::ChecksumMismatch)
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        assert_eq!(
+            Snapshot::from_bytes(&corrupted).err(),
+            Some(SnapshotError::BadMagic)
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[8] = 0xFF;
+        assert_eq!(
+            Snapshot::from_bytes(&corrupted).err(),
+            Some(SnapshotError::UnsupportedVersion(0x00FF))
+        );
+
+        assert_eq!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 1]).err(),
+            Some(SnapshotError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_invalid_state() {
+        let mut snapshot = running().snapshot();
+        snapshot.state.memory_size = 0x800;
+        assert_eq!(
+            Snapshot::from_bytes(&snapshot.to_bytes()).err(),
+            Some(SnapshotError::Invalid)
+        );
+
+        let mut snapshot = running().snapshot();
+        snapshot.state.screen.planes = 0b100;
+        assert_eq!(
+            Snapshot::from_bytes(&snapshot.to_bytes()).err(),
+            Some(SnapshotError::Invalid)
+        );
+    }
+}
@@ -14,5 +14,7 @@ fn main() {
         "#,
     );
 
-    println!("{:?}", lexer.collect::<Vec<_>>())
+    for token in lexer {
+        println!("{}:{} {:?}", token.line(), token.column(), token.value());
+    }
 }
@@ -1,12 +1,18 @@
-use crate::token::{Delimeter, Token};
+use crate::span::Spanned;
+use crate::token::{Delimeter, Operator, Token};
 use core::iter::Peekable;
 use core::str::CharIndices;
 use itertools::Itertools;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Lexer<'l> {
     input: &'l str,
     iter: Peekable<CharIndices<'l>>,
+    // Line of the last token and the offset it was counted up to.
+    line: usize,
+    scanned: usize,
+    // Whether comments, whitespace and newlines are tokens too.
+    trivia: bool,
 }
 
 impl<'c> From<&'c str> for Lexer<'c> {
@@ -14,16 +20,79 @@ impl<'c> From<&'c str> for Lexer<'c> {
         Self {
             input,
             iter: input.char_indices().peekable(),
+            line: 1,
+            scanned: 0,
+            trivia: false,
         }
     }
 }
 
 impl<'l> Lexer<'l> {
-    fn skip_comment(&mut self) {
+    // Keeps comments, whitespace and newlines, the texts of all tokens together are the input.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        self
+    }
+
+    // One based line of the last returned token.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn input(&self) -> &'l str {
+        self.input
+    }
+
+    fn advance_line(&mut self, pos: usize) {
+        self.line += self.input[self.scanned..pos].matches('\n').count();
+        self.scanned = pos;
+    }
+
+    fn at_newline(&self, pos: usize) -> bool {
+        self.input[pos..].starts_with('\n') || self.input[pos..].starts_with("\r\n")
+    }
+
+    // Rest of the line, without the newline ending it.
+    fn lex_until_newline(&mut self, head: usize, is_part: fn(char) -> bool) -> &'l str {
+        while let Some(&(pos, c)) = self.iter.peek() {
+            if !is_part(c) || self.at_newline(pos) {
+                break;
+            }
+
+            self.iter.next();
+        }
+
+        let tail = self.iter.peek().map_or(self.input.len(), |&(pos, _)| pos);
+
+        &self.input[head..tail]
+    }
+
+    fn lex_string(&mut self, head: usize) -> Token<'l> {
         self.iter
             .by_ref()
-            .peeking_take_while(|&(_, c)| c != '\n')
+            .peeking_take_while(|&(_, c)| c != '"' && c != '\n')
             .for_each(drop);
+
+        let tail = self.iter.peek().map_or(self.input.len(), |&(pos, _)| pos);
+
+        match self.iter.next_if(|&(_, c)| c == '"') {
+            Some(_) => Token::String(&self.input[head + 1..tail]),
+            None => Token::Unknown(&self.input[head..tail]),
+        }
+    }
+
+    fn lex_operator(&mut self, head: usize, c: char) -> Token<'l> {
+        // Shifts are the only operators made of two characters.
+        let tail = match self
+            .iter
+            .next_if(|&(_, next)| next == c && matches!(c, '<' | '>'))
+        {
+            Some((pos, _)) => pos,
+            None => head,
+        };
+
+        let s = &self.input[head..=tail];
+        Operator::try_from(s).map_or(Token::Unknown(s), Token::Operator)
     }
 
     fn lex_token(&mut self, head: usize) -> Token<'l> {
@@ -31,7 +100,7 @@ impl<'l> Lexer<'l> {
             + self
                 .iter
                 .by_ref()
-                .peeking_take_while(|&(_, c)| c.is_ascii_alphanumeric())
+                .peeking_take_while(|&(_, c)| is_word(c))
                 .count();
 
         match &self.input[head..=tail] {
@@ -39,27 +108,174 @@ impl<'l> Lexer<'l> {
                 let number = u16::from_str_radix(&s[2..], 16).ok();
                 number.map_or(Token::Unknown(s), Token::Number)
             }
-            s => Token::try_from(s).unwrap_or(Token::Unknown(s)),
+            s => Token::try_from(s).unwrap_or_else(|_| {
+                if s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                    Token::Identifier(s)
+                } else {
+                    Token::Unknown(s)
+                }
+            }),
         }
     }
 }
 
 impl<'l> Iterator for Lexer<'l> {
-    type Item = Token<'l>;
+    type Item = Spanned<'l, Token<'l>>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let trivia = self.trivia;
+
         self.iter
             .by_ref()
-            .skip_while(|(_, c)| c.is_whitespace())
-            .next()
+            .find(|(_, c)| trivia || !c.is_whitespace())
             .and_then(|(pos, c)| match c {
-                ';' => {
-                    self.skip_comment();
+                ';' if !trivia => {
+                    self.lex_until_newline(pos, |_| true);
                     self.next()
                 }
-                ',' => Some(Token::Delimeter(Delimeter::Comma)),
-                c if c.is_ascii_alphanumeric() => Some(self.lex_token(pos)),
-                _ => Some(Token::Unknown(&self.input[pos..=pos])),
+                c => {
+                    self.advance_line(pos);
+
+                    let token = match c {
+                        ';' => Token::Comment(self.lex_until_newline(pos, |_| true)),
+                        '\r' | '\n' if self.at_newline(pos) => {
+                            self.iter.next_if(|_| c == '\r');
+                            Token::Newline
+                        }
+                        c if c.is_whitespace() => {
+                            Token::Whitespace(self.lex_until_newline(pos, char::is_whitespace))
+                        }
+                        ',' => Token::Delimeter(Delimeter::Comma),
+                        ':' => Token::Delimeter(Delimeter::Colon),
+                        '(' => Token::Delimeter(Delimeter::LeftParen),
+                        ')' => Token::Delimeter(Delimeter::RightParen),
+                        '[' => Token::Delimeter(Delimeter::LeftBracket),
+                        ']' => Token::Delimeter(Delimeter::RightBracket),
+                        '"' => self.lex_string(pos),
+                        '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^' | '~' | '<' | '>' => {
+                            self.lex_operator(pos, c)
+                        }
+                        c if is_word(c) => self.lex_token(pos),
+                        _ => Token::Unknown(&self.input[pos..pos + c.len_utf8()]),
+                    };
+
+                    let tail = self.iter.peek().map_or(self.input.len(), |&(pos, _)| pos);
+
+                    Some(Spanned::new(token, pos..tail, self.input))
+                }
             })
     }
 }
+
+// Characters of mnemonics, registers, numbers and identifiers.
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lexer;
+    use crate::token::{Delimeter, Directive, Mnemonic, Register, Token};
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_spans() {
+        let source = "cls ; clear\n  se v0, 0x20\n\"ü\" ü";
+        let tokens: Vec<_> = Lexer::from(source).collect();
+
+        let spans: Vec<_> = tokens.iter().map(|token| token.span()).collect();
+        assert_eq!(
+            spans,
+            [0..3, 14..16, 17..19, 19..20, 21..25, 26..30, 31..33]
+        );
+
+        assert_eq!(tokens[2].value(), &Token::Register(Register::V0));
+        assert_eq!(tokens[3].value(), &Token::Delimeter(Delimeter::Comma));
+        assert_eq!(tokens[0].value(), &Token::Mnemonic(Mnemonic::Cls));
+        assert_eq!(tokens[4].text(), "0x20");
+        assert_eq!((tokens[1].line(), tokens[1].column()), (2, 3));
+        assert_eq!((tokens[6].line(), tokens[6].column()), (3, 5));
+        assert_eq!(tokens[6].value(), &Token::Unknown("ü"));
+
+        // Everything but identifiers is case insensitive.
+        let tokens: Vec<_> = Lexer::from("CLS V0 Loop")
+            .map(|token| token.into_value())
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::Mnemonic(Mnemonic::Cls),
+                Token::Register(Register::V0),
+                Token::Identifier("Loop")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trivia_round_trip() {
+        let sources = [
+            "",
+            "; Looping program that does nothing\n\nloop:\tcls   ; clear\n  jmp loop\n",
+            "\r\nstart: ld v0, 0x10 ;\r\n\r\n  db \"text\", (1 << 2) ; trailing",
+            "unterminated \"string\n\t\r  @ ü ;no newline",
+        ];
+
+        for source in sources {
+            let text: String = Lexer::from(source)
+                .with_trivia()
+                .map(|token| token.text())
+                .collect();
+
+            assert_eq!(text, source);
+
+            // Without the trivia the tokens are the ones lexed by default.
+            let tokens: Vec<_> = Lexer::from(source)
+                .with_trivia()
+                .filter(|token| {
+                    !matches!(
+                        token.value(),
+                        Token::Comment(_) | Token::Whitespace(_) | Token::Newline
+                    )
+                })
+                .collect();
+
+            assert_eq!(tokens, Lexer::from(source).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_trivia() {
+        let tokens: Vec<_> = Lexer::from("cls ; clear\r\n\tret")
+            .with_trivia()
+            .map(|token| (token.line(), token.into_value()))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            [
+                (1, Token::Mnemonic(Mnemonic::Cls)),
+                (1, Token::Whitespace(" ")),
+                (1, Token::Comment("; clear")),
+                (1, Token::Newline),
+                (2, Token::Whitespace("\t")),
+                (2, Token::Mnemonic(Mnemonic::Ret)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let source = "loop: ld [i], vf ; save\n\tdb \"text\", (1 << 2) % hi(loop)\n\
+                      ld i, long 4096\nfill 3, ~255 & 7 @";
+
+        // Lowercase with decimal numbers, every token is written like in the source.
+        let text: String = Lexer::from(source)
+            .with_trivia()
+            .map(|token| token.value().to_string())
+            .collect();
+
+        assert_eq!(text, source);
+        assert_eq!(Token::Directive(Directive::Byte).to_string(), "db");
+    }
+}
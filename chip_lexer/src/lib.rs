@@ -1,4 +1,8 @@
 #![no_std]
 
+#[cfg(test)]
+extern crate alloc;
+
 pub mod lexer;
+pub mod span;
 pub mod token;
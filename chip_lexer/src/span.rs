@@ -0,0 +1,74 @@
+use core::fmt::{Debug, Formatter};
+use core::ops::Range;
+
+// A value lexed from `source`, with the bytes it came from.
+#[derive(Clone, PartialEq)]
+pub struct Spanned<'s, T> {
+    value: T,
+    start: usize,
+    end: usize,
+    source: &'s str,
+}
+
+impl<'s, T> Spanned<'s, T> {
+    pub fn new(value: T, span: Range<usize>, source: &'s str) -> Self {
+        Self {
+            value,
+            start: span.start,
+            end: span.end,
+            source,
+        }
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    // The source text of the value.
+    pub fn text(&self) -> &'s str {
+        &self.source[self.start..self.end]
+    }
+
+    // One based line of the start, counted when asked for.
+    pub fn line(&self) -> usize {
+        position(self.source, self.start).0
+    }
+
+    // One based column of the start in characters, counted when asked for.
+    pub fn column(&self) -> usize {
+        position(self.source, self.start).1
+    }
+}
+
+impl<'s, T: Debug> Debug for Spanned<'s, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?} @ {}..{}", self.value, self.start, self.end)
+    }
+}
+
+// One based line and column of the byte `offset` in `source`.
+pub fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
@@ -1,12 +1,21 @@
-use core::fmt::Display;
+use core::fmt::{Display, Formatter};
 use core::ops::Shl;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token<'t> {
     Delimeter(Delimeter),
+    Operator(Operator),
     Mnemonic(Mnemonic),
+    Directive(Directive),
     Number(u16),
     Register(Register),
+    Keyword(Keyword),
+    Identifier(&'t str),
+    String(&'t str),
+    // Trivia, only lexed when asked for.
+    Comment(&'t str),
+    Whitespace(&'t str),
+    Newline,
     Unknown(&'t str),
 }
 
@@ -14,35 +23,79 @@ impl<'t> TryFrom<&str> for Token<'t> {
     type Error = ();
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        // Keywords are case insensitive, none of them is longer than the buffer.
+        let mut buffer = [0; 8];
+        let value = match buffer.get_mut(..value.len()) {
+            Some(bytes) => {
+                bytes.copy_from_slice(value.as_bytes());
+                bytes.make_ascii_lowercase();
+                core::str::from_utf8(bytes).unwrap_or(value)
+            }
+            None => value,
+        };
+
         if let Ok(delimeter) = Delimeter::try_from(value) {
             Ok(Token::Delimeter(delimeter))
         } else if let Ok(instruction) = Mnemonic::try_from(value) {
             Ok(Token::Mnemonic(instruction))
+        } else if let Ok(directive) = Directive::try_from(value) {
+            Ok(Token::Directive(directive))
         } else if let Ok(number) = value.parse::<u16>() {
             Ok(Token::Number(number))
         } else if let Ok(register) = Register::try_from(value) {
             Ok(Token::Register(register))
+        } else if let Ok(keyword) = Keyword::try_from(value) {
+            Ok(Token::Keyword(keyword))
         } else {
             Err(())
         }
     }
 }
 
+// The token as it could be written in the source.
 impl<'t> Display for Token<'t> {
-    fn fmt(&self, _f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         match self {
-            Token::Delimeter(_) => todo!(),
-            Token::Mnemonic(_) => todo!(),
-            Token::Number(_) => todo!(),
-            Token::Register(_) => todo!(),
-            Token::Unknown(_) => todo!(),
+            Token::Delimeter(delimeter) => write!(f, "{}", delimeter),
+            Token::Operator(operator) => write!(f, "{}", operator),
+            Token::Mnemonic(mnemonic) => write!(f, "{}", mnemonic),
+            Token::Directive(directive) => write!(f, "{}", directive),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Register(register) => write!(f, "{}", register),
+            Token::Keyword(keyword) => write!(f, "{}", keyword),
+            Token::Identifier(text)
+            | Token::Comment(text)
+            | Token::Whitespace(text)
+            | Token::Unknown(text) => write!(f, "{}", text),
+            Token::String(string) => write!(f, "\"{}\"", string),
+            Token::Newline => writeln!(f),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Delimeter {
     Comma,
+    Colon,
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+}
+
+impl Display for Delimeter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let delimeter = match self {
+            Delimeter::Comma => ",",
+            Delimeter::Colon => ":",
+            Delimeter::LeftParen => "(",
+            Delimeter::RightParen => ")",
+            Delimeter::LeftBracket => "[",
+            Delimeter::RightBracket => "]",
+        };
+
+        write!(f, "{}", delimeter)
+    }
 }
 
 impl<'t> TryFrom<&'t str> for Delimeter {
@@ -51,6 +104,11 @@ impl<'t> TryFrom<&'t str> for Delimeter {
     fn try_from(value: &'t str) -> Result<Self, Self::Error> {
         let delimeter = match value {
             "," => Delimeter::Comma,
+            ":" => Delimeter::Colon,
+            "(" => Delimeter::LeftParen,
+            ")" => Delimeter::RightParen,
+            "[" => Delimeter::LeftBracket,
+            "]" => Delimeter::RightBracket,
             _ => Err(())?,
         };
 
@@ -58,7 +116,65 @@ impl<'t> TryFrom<&'t str> for Delimeter {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let operator = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Star => "*",
+            Operator::Slash => "/",
+            Operator::Percent => "%",
+            Operator::Ampersand => "&",
+            Operator::Pipe => "|",
+            Operator::Caret => "^",
+            Operator::Tilde => "~",
+            Operator::ShiftLeft => "<<",
+            Operator::ShiftRight => ">>",
+        };
+
+        write!(f, "{}", operator)
+    }
+}
+
+impl TryFrom<&str> for Operator {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let operator = match value {
+            "+" => Operator::Plus,
+            "-" => Operator::Minus,
+            "*" => Operator::Star,
+            "/" => Operator::Slash,
+            "%" => Operator::Percent,
+            "&" => Operator::Ampersand,
+            "|" => Operator::Pipe,
+            "^" => Operator::Caret,
+            "~" => Operator::Tilde,
+            "<<" => Operator::ShiftLeft,
+            ">>" => Operator::ShiftRight,
+            _ => Err(())?,
+        };
+
+        Ok(operator)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Mnemonic {
     Cls,
     Ret,
@@ -80,6 +196,17 @@ pub enum Mnemonic {
     Drw,
     Skp,
     Sknp,
+    Scd,
+    Scr,
+    Scl,
+    Exit,
+    Low,
+    High,
+    Save,
+    Load,
+    Plane,
+    Audio,
+    Pitch,
 }
 
 impl TryFrom<&str> for Mnemonic {
@@ -107,6 +234,17 @@ impl TryFrom<&str> for Mnemonic {
             "drw" => Mnemonic::Drw,
             "skp" => Mnemonic::Skp,
             "sknp" => Mnemonic::Sknp,
+            "scd" => Mnemonic::Scd,
+            "scr" => Mnemonic::Scr,
+            "scl" => Mnemonic::Scl,
+            "exit" => Mnemonic::Exit,
+            "low" => Mnemonic::Low,
+            "high" => Mnemonic::High,
+            "save" => Mnemonic::Save,
+            "load" => Mnemonic::Load,
+            "plane" => Mnemonic::Plane,
+            "audio" => Mnemonic::Audio,
+            "pitch" => Mnemonic::Pitch,
             _ => Err(())?,
         };
 
@@ -115,12 +253,152 @@ impl TryFrom<&str> for Mnemonic {
 }
 
 impl Display for Mnemonic {
-    fn fmt(&self, _f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        todo!()
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mnemonic = match self {
+            Mnemonic::Cls => "cls",
+            Mnemonic::Ret => "ret",
+            Mnemonic::Sys => "sys",
+            Mnemonic::Jmp => "jmp",
+            Mnemonic::Call => "call",
+            Mnemonic::Se => "se",
+            Mnemonic::Sne => "sne",
+            Mnemonic::Ld => "ld",
+            Mnemonic::Add => "add",
+            Mnemonic::Or => "or",
+            Mnemonic::And => "and",
+            Mnemonic::Xor => "xor",
+            Mnemonic::Sub => "sub",
+            Mnemonic::Shr => "shr",
+            Mnemonic::Subn => "subn",
+            Mnemonic::Shl => "shl",
+            Mnemonic::Rnd => "rnd",
+            Mnemonic::Drw => "drw",
+            Mnemonic::Skp => "skp",
+            Mnemonic::Sknp => "sknp",
+            Mnemonic::Scd => "scd",
+            Mnemonic::Scr => "scr",
+            Mnemonic::Scl => "scl",
+            Mnemonic::Exit => "exit",
+            Mnemonic::Low => "low",
+            Mnemonic::High => "high",
+            Mnemonic::Save => "save",
+            Mnemonic::Load => "load",
+            Mnemonic::Plane => "plane",
+            Mnemonic::Audio => "audio",
+            Mnemonic::Pitch => "pitch",
+        };
+
+        write!(f, "{}", mnemonic)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Directive {
+    Equ,
+    Define,
+    Org,
+    Byte,
+    Word,
+    Align,
+    Fill,
+    Incbin,
+    Include,
+}
+
+// The first of the names of each directive.
+impl Display for Directive {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let directive = match self {
+            Directive::Equ => "equ",
+            Directive::Define => "define",
+            Directive::Org => "org",
+            Directive::Byte => "db",
+            Directive::Word => "dw",
+            Directive::Align => "align",
+            Directive::Fill => "fill",
+            Directive::Incbin => "incbin",
+            Directive::Include => "include",
+        };
+
+        write!(f, "{}", directive)
+    }
+}
+
+impl TryFrom<&str> for Directive {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let directive = match value {
+            "equ" => Directive::Equ,
+            "define" => Directive::Define,
+            "org" => Directive::Org,
+            "db" | "byte" => Directive::Byte,
+            "dw" | "word" => Directive::Word,
+            "align" => Directive::Align,
+            "fill" | "ds" => Directive::Fill,
+            "incbin" => Directive::Incbin,
+            "include" => Directive::Include,
+            _ => Err(())?,
+        };
+
+        Ok(directive)
+    }
+}
+
+// Operands of `ld` and `add` other than the V registers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Keyword {
+    I,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long,
+}
+
+impl Display for Keyword {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let keyword = match self {
+            Keyword::I => "i",
+            Keyword::Dt => "dt",
+            Keyword::St => "st",
+            Keyword::K => "k",
+            Keyword::F => "f",
+            Keyword::Hf => "hf",
+            Keyword::B => "b",
+            Keyword::R => "r",
+            Keyword::Long => "long",
+        };
+
+        write!(f, "{}", keyword)
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl TryFrom<&str> for Keyword {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let keyword = match value {
+            "i" => Keyword::I,
+            "dt" => Keyword::Dt,
+            "st" => Keyword::St,
+            "k" => Keyword::K,
+            "f" => Keyword::F,
+            "hf" => Keyword::Hf,
+            "b" => Keyword::B,
+            "r" => Keyword::R,
+            "long" => Keyword::Long,
+            _ => Err(())?,
+        };
+
+        Ok(keyword)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
     V0 = 0x0,
     V1 = 0x1,
@@ -140,6 +418,12 @@ pub enum Register {
     Vf = 0xF,
 }
 
+impl Display for Register {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "v{:x}", *self as u8)
+    }
+}
+
 impl Shl<u16> for Register {
     type Output = u16;
 
@@ -149,6 +433,34 @@ impl Shl<u16> for Register {
     }
 }
 
+impl TryFrom<u8> for Register {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let register = match value {
+            0x0 => Register::V0,
+            0x1 => Register::V1,
+            0x2 => Register::V2,
+            0x3 => Register::V3,
+            0x4 => Register::V4,
+            0x5 => Register::V5,
+            0x6 => Register::V6,
+            0x7 => Register::V7,
+            0x8 => Register::V8,
+            0x9 => Register::V9,
+            0xA => Register::Va,
+            0xB => Register::Vb,
+            0xC => Register::Vc,
+            0xD => Register::Vd,
+            0xE => Register::Ve,
+            0xF => Register::Vf,
+            _ => Err(())?,
+        };
+
+        Ok(register)
+    }
+}
+
 impl TryFrom<&str> for Register {
     type Error = ();
 
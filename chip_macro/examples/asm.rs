@@ -1,7 +1,7 @@
 use chip_macro::asm;
 
 fn main() {
-    let program: &[u16] = asm!(
+    let program: &[u8] = asm!(
         "
           ; Looping program that does nothing
 
@@ -1,21 +1,57 @@
 use chip_assembler::{assembler::Assembler, parser::Parser};
 use chip_lexer::lexer::Lexer;
 use proc_macro::TokenStream;
-use quote::quote;
+use proc_macro2::Span;
+use quote::{quote, quote_spanned};
 use syn::{parse_macro_input, LitStr};
 
 #[proc_macro]
 pub fn asm(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as LitStr);
-    let asm_code = input.value();
 
-    let lexer = Lexer::from(asm_code.as_str());
+    expand(&input.value(), input.span()).into()
+}
+
+// The bytes of `asm_code`, or a `compile_error!` at `span` with every diagnostic.
+fn expand(asm_code: &str, span: Span) -> proc_macro2::TokenStream {
+    let lexer = Lexer::from(asm_code);
     let parser = Parser::from(lexer);
 
-    let output = Assembler::from(parser).collect::<Vec<u16>>();
+    match Assembler::from(parser).assemble() {
+        Ok(program) => {
+            let output = program.bytes();
+
+            quote! {
+               &[#(#output), *]
+            }
+        }
+        Err(diagnostics) => {
+            let message = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.render(asm_code))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+
+            quote_spanned! {span=>
+                compile_error!(#message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use proc_macro2::Span;
+
+    #[test]
+    fn test_expand() {
+        let output = expand("cls\njmp 0x200", Span::call_site()).to_string();
+        assert_eq!(output, "& [0u8 , 224u8 , 18u8 , 0u8]");
 
-    quote! {
-       &[#(#output), *]
+        let output = expand("jmp\ncls\nld v0, nowhere", Span::call_site()).to_string();
+        assert!(output.starts_with("compile_error !"));
+        assert!(output.contains("The line ended in the middle of a statement"));
+        assert!(output.contains("The symbol nowhere isn't defined"));
     }
-    .into()
 }
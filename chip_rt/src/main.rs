@@ -1,13 +1,19 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use anyhow::Result;
+use chip_interpreter::framebuffer::{Framebuffer, Region};
+use chip_interpreter::host::{Audio, Display, Input};
 use chip_interpreter::interpreter::{Interpreter, InterpreterEvent};
-use pixels::{Pixels, SurfaceTexture};
+use chip_interpreter::rewind::Rewind;
+use chip_interpreter::snapshot::Snapshot;
+use pixels::{Pixels, SurfaceTexture, TextureError};
 use rodio::source::SineWave;
 use rodio::{OutputStream, Sink};
+use std::fs;
+use std::time::{Duration, Instant};
 use tokio::main;
 use winit::dpi::LogicalSize;
-use winit::event::{Event, StartCause, WindowEvent};
+use winit::event::{ElementState, Event, KeyboardInput, StartCause, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
 
@@ -28,50 +34,117 @@ async fn main() -> Result<()> {
 
     let size = window.inner_size();
 
-    let mut pixels = {
+    let pixels = {
         let surface_texture = SurfaceTexture::new(size.width, size.height, &window);
         Pixels::new_async(64, 32, surface_texture).await?
     };
 
-    let mut interpreter = Interpreter::default();
+    let mut interpreter = Rewind::from(Interpreter::default());
 
     let program = &[0x60, 0x04, 0xF0, 0x18, 0x00, 0xE0, 0x12, 0x04];
 
-    interpreter.load(program);
+    interpreter.load(program)?;
+
+    let (_stream, stream_handle) = OutputStream::try_default()?;
+    let sink = Sink::try_new(&stream_handle)?;
+    sink.append(SineWave::new(440.0));
+    sink.pause();
+
+    let mut frontend = Frontend {
+        pixels,
+        sink,
+        keys: [false; 16],
+        resolution: (64, 32),
+        error: None,
+    };
+
+    // The timers and the display run at 60 Hz, the CPU runs a fixed amount of instructions per frame.
+    let frame_duration = Duration::from_nanos(1_000_000_000 / 60);
+    let mut next_frame = Instant::now();
+    let mut slot = 1;
+    let mut rewinding = false;
 
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
+        *control_flow = ControlFlow::WaitUntil(next_frame);
 
         match event {
-            Event::NewEvents(StartCause::Poll) => match interpreter.cycle() {
-                InterpreterEvent::Audio => {
-                    println!("AUDIO");
+            Event::NewEvents(StartCause::Init | StartCause::ResumeTimeReached { .. }) => {
+                if rewinding {
+                    interpreter.step_back_frame();
+                    interpreter.present(&mut frontend);
+                } else {
+                    match interpreter.run_frame_with(&mut frontend) {
+                        Ok(InterpreterEvent::Exit) => *control_flow = ControlFlow::Exit,
+                        Ok(_) => (),
+                        Err(err) => {
+                            eprintln!("interpreter: {}", err);
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                    }
+                }
 
-                    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-                    let sink = Sink::try_new(&stream_handle).unwrap();
+                if let Some(err) = frontend.error.take() {
+                    eprintln!("pixels: {}", err);
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
 
-                    let source = SineWave::new(440.0);
-                    sink.append(source);
+                window.request_redraw();
 
-                    std::thread::sleep(std::time::Duration::from_millis(500));
-                }
-                _ => (),
-            },
-            Event::MainEventsCleared => {
-                interpreter
-                    .screen_buffer
-                    .iter()
-                    .zip(pixels.frame_mut().chunks_exact_mut(4))
-                    .for_each(|(&pixel, chunk)| {
-                        let color = pixel * 255;
-                        chunk.copy_from_slice(&[color, color, color, 255]);
-                    });
-
-                if let Err(err) = pixels.render() {
+                next_frame += frame_duration;
+                *control_flow = ControlFlow::WaitUntil(next_frame);
+            }
+            Event::RedrawRequested(_) => {
+                if let Err(err) = frontend.pixels.render() {
                     eprintln!("pixels: {}", err);
                     *control_flow = ControlFlow::Exit;
                 };
             }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(keycode),
+                                state,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(key) = keypad_key(keycode) {
+                    frontend.keys[key as usize] = state == ElementState::Pressed;
+                }
+
+                // Holding backspace runs the game backwards.
+                if keycode == VirtualKeyCode::Back {
+                    rewinding = state == ElementState::Pressed;
+                }
+
+                // F1-F4 select a quick save slot, F5 saves to it and F9 loads from it.
+                if state == ElementState::Pressed {
+                    match keycode {
+                        VirtualKeyCode::F1 => slot = 1,
+                        VirtualKeyCode::F2 => slot = 2,
+                        VirtualKeyCode::F3 => slot = 3,
+                        VirtualKeyCode::F4 => slot = 4,
+                        VirtualKeyCode::F5 => {
+                            let bytes = interpreter.snapshot().to_bytes();
+
+                            if let Err(err) = fs::write(slot_path(slot), bytes) {
+                                eprintln!("save state: {}", err);
+                            }
+                        }
+                        VirtualKeyCode::F9 => match load_slot(slot) {
+                            Ok(snapshot) => interpreter.restore(&snapshot),
+                            Err(err) => eprintln!("save state: {}", err),
+                        },
+                        _ => (),
+                    }
+                }
+            }
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
@@ -80,3 +153,101 @@ async fn main() -> Result<()> {
         }
     });
 }
+
+// Window, speaker and keyboard of the interpreter.
+struct Frontend {
+    pixels: Pixels,
+    sink: Sink,
+    keys: [bool; 16],
+    resolution: (u32, u32),
+    error: Option<TextureError>,
+}
+
+impl Display for Frontend {
+    fn frame_ready(&mut self, screen: &Framebuffer, region: Region) {
+        let size = (screen.width() as u32, screen.height() as u32);
+
+        if size != self.resolution {
+            if let Err(err) = self.pixels.resize_buffer(size.0, size.1) {
+                self.error = Some(err);
+                return;
+            }
+
+            self.resolution = size;
+        }
+
+        let width = screen.width();
+        let frame = self.pixels.frame_mut();
+
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let offset = (y * width + x) * 4;
+                let colour = &PALETTE[screen.pixel(x, y) as usize & 3];
+
+                frame[offset..offset + 4].copy_from_slice(colour);
+            }
+        }
+    }
+}
+
+impl Audio for Frontend {
+    fn buzzer_on(&mut self, _pattern: &[u8; 16], _pitch: u8) {
+        self.sink.play();
+    }
+
+    fn buzzer_off(&mut self) {
+        self.sink.pause();
+    }
+}
+
+impl Input for Frontend {
+    fn is_key_pressed(&mut self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+}
+
+// Colours for the four combinations of the two XO-CHIP planes.
+const PALETTE: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+];
+
+fn slot_path(slot: u8) -> String {
+    format!("quicksave{}.sav", slot)
+}
+
+fn load_slot(slot: u8) -> Result<Snapshot> {
+    Ok(Snapshot::from_bytes(&fs::read(slot_path(slot))?)?)
+}
+
+// Maps the left side of a QWERTY keyboard onto the COSMAC VIP hex keypad:
+//
+// 1 2 3 4      1 2 3 C
+// Q W E R  ->  4 5 6 D
+// A S D F      7 8 9 E
+// Z X C V      A 0 B F
+fn keypad_key(keycode: VirtualKeyCode) -> Option<u8> {
+    let key = match keycode {
+        VirtualKeyCode::Key1 => 0x1,
+        VirtualKeyCode::Key2 => 0x2,
+        VirtualKeyCode::Key3 => 0x3,
+        VirtualKeyCode::Key4 => 0xC,
+        VirtualKeyCode::Q => 0x4,
+        VirtualKeyCode::W => 0x5,
+        VirtualKeyCode::E => 0x6,
+        VirtualKeyCode::R => 0xD,
+        VirtualKeyCode::A => 0x7,
+        VirtualKeyCode::S => 0x8,
+        VirtualKeyCode::D => 0x9,
+        VirtualKeyCode::F => 0xE,
+        VirtualKeyCode::Z => 0xA,
+        VirtualKeyCode::X => 0x0,
+        VirtualKeyCode::C => 0xB,
+        VirtualKeyCode::V => 0xF,
+        _ => return None,
+    };
+
+    Some(key)
+}